@@ -1,7 +1,43 @@
+const DEFAULT_SERVER_URL = 'ws://localhost:8765';
+// Version of the server <-> extension messages; must match PROTOCOL_VERSION in
+// the server's protocol.rs. The message shapes are in protocol.schema.json.
+const PROTOCOL_VERSION = 1;
+
 let ws = null;
 let reconnectInterval = null;
+// Set once the server accepts our hello; a socket can be open but unpaired.
+let authenticated = false;
+// The server rejected our pairing token. Stop reconnecting until a new token
+// is saved in the popup.
+let authError = null;
+// The server speaks another protocol version. Reconnecting cannot help until
+// the extension or the server is updated.
+let protocolError = null;
+// Tab each long-running content-script request is working in, by requestId,
+// so a `cancel` from the server can be forwarded to it.
+const inFlight = new Map();
+
+// Answers a request. Replies repeat the request's type and requestId; the
+// body's own `success: false` marks a failure.
+function reply(message, body) {
+  if (ws && ws.readyState === WebSocket.OPEN) {
+    ws.send(JSON.stringify({ type: message.type, requestId: message.requestId, success: true, ...body }));
+  }
+}
 
-function handleExtensionError(requestId, context, responseType) {
+function replyError(message, error, details) {
+  reply(message, { success: false, error, ...details });
+}
+
+// Pushes a browser event (see BrowserEvent in protocol.rs) to the server.
+// Events that happen while disconnected are dropped.
+function sendEvent(event) {
+  if (ws && ws.readyState === WebSocket.OPEN && authenticated) {
+    ws.send(JSON.stringify({ type: 'event', ...event }));
+  }
+}
+
+function handleExtensionError(message, context) {
   if (chrome.runtime.lastError) {
     const error = chrome.runtime.lastError;
     const errorMsg = error.message;
@@ -13,42 +49,190 @@ function handleExtensionError(requestId, context, responseType) {
       console.warn('[BrowseHand] 💡 Tip: The content script might not be injected. Try reloading the web page.');
     }
     
-    if (ws && ws.readyState === WebSocket.OPEN) {
-      if (responseType) {
-        ws.send(JSON.stringify({ 
-          type: responseType, 
-          requestId: requestId, 
-          success: false, 
-          error: userMsg 
-        }));
+    replyError(message, userMsg);
+    return true;
+  }
+  return false;
+}
+
+function serializeTab(tab) {
+  return {
+    id: tab.id,
+    windowId: tab.windowId,
+    index: tab.index,
+    title: tab.title,
+    url: tab.url,
+    active: tab.active,
+    status: tab.status
+  };
+}
+
+// Resolves the tab a request targets: the explicit message.tabId if given,
+// otherwise the active tab of the current window.
+async function getTargetTab(message) {
+  const tabId = message.tabId;
+  try {
+    if (tabId !== undefined && tabId !== null) {
+      return await chrome.tabs.get(tabId);
+    }
+    const tabs = await chrome.tabs.query({ active: true, currentWindow: true });
+    if (tabs[0]) {
+      return tabs[0];
+    }
+    throw new Error('No active tab');
+  } catch (error) {
+    console.error('[BrowseHand] Failed to resolve target tab:', error);
+    if (tabId != null) {
+      replyError(message, `Tab not found: ${tabId}`, { code: 'tab_not_found', tabId });
+    } else {
+      replyError(message, error.message);
+    }
+    return null;
+  }
+}
+
+// Name this browser profile announces to the MCP server, so several profiles
+// can be connected at once and addressed by name.
+async function getSessionName() {
+  const { sessionName } = await chrome.storage.local.get('sessionName');
+  if (sessionName) {
+    return sessionName;
+  }
+  const generated = `browser-${crypto.randomUUID().slice(0, 8)}`;
+  await chrome.storage.local.set({ sessionName: generated });
+  return generated;
+}
+
+function sendToTab(tabId, message) {
+  return new Promise((resolve, reject) => {
+    chrome.tabs.sendMessage(tabId, message, (response) => {
+      if (chrome.runtime.lastError) {
+        reject(new Error(chrome.runtime.lastError.message));
+      } else if (response && response.success === false) {
+        const error = new Error(response.error);
+        error.code = response.code;
+        error.selector = response.selector;
+        reject(error);
       } else {
-        ws.send(JSON.stringify({ 
-          type: 'error', 
-          requestId: requestId, 
-          message: userMsg 
-        }));
+        resolve(response);
       }
+    });
+  });
+}
+
+async function blobToBase64(blob) {
+  const bytes = new Uint8Array(await blob.arrayBuffer());
+  let binary = '';
+  for (let i = 0; i < bytes.length; i += 0x8000) {
+    binary += String.fromCharCode.apply(null, bytes.subarray(i, i + 0x8000));
+  }
+  return btoa(binary);
+}
+
+async function captureBitmap(windowId, options) {
+  const dataUrl = await chrome.tabs.captureVisibleTab(windowId, options);
+  const blob = await (await fetch(dataUrl)).blob();
+  return createImageBitmap(blob);
+}
+
+// Chrome allows only a couple of captureVisibleTab calls per second
+const CAPTURE_INTERVAL_MS = 600;
+// Canvas dimensions beyond this fail to allocate in Chrome
+const MAX_CANVAS_HEIGHT = 16384;
+
+async function captureCanvas(tab, payload) {
+  const captureOptions = { format: 'png' };
+  let canvas;
+  if (payload.mode === 'element') {
+    const { rect, dpr } = await sendToTab(tab.id, { action: 'get_element_rect', selector: payload.selector });
+    await new Promise((resolve) => setTimeout(resolve, 100));
+    const bitmap = await captureBitmap(tab.windowId, captureOptions);
+    const width = Math.max(1, Math.round(Math.min(rect.width * dpr, bitmap.width - rect.x * dpr)));
+    const height = Math.max(1, Math.round(Math.min(rect.height * dpr, bitmap.height - rect.y * dpr)));
+    canvas = new OffscreenCanvas(width, height);
+    canvas.getContext('2d').drawImage(bitmap, rect.x * dpr, rect.y * dpr, width, height, 0, 0, width, height);
+  } else if (payload.mode === 'full_page') {
+    const metrics = await sendToTab(tab.id, { action: 'get_page_metrics' });
+    const dpr = metrics.dpr;
+    const totalHeight = Math.min(Math.round(metrics.scrollHeight * dpr), MAX_CANVAS_HEIGHT);
+    canvas = new OffscreenCanvas(Math.round(metrics.viewportWidth * dpr), totalHeight);
+    const ctx = canvas.getContext('2d');
+    try {
+      for (let y = 0; y * dpr < totalHeight; y += metrics.viewportHeight) {
+        const { scrollY } = await sendToTab(tab.id, { action: 'scroll_to', y });
+        await new Promise((resolve) => setTimeout(resolve, CAPTURE_INTERVAL_MS));
+        const bitmap = await captureBitmap(tab.windowId, captureOptions);
+        ctx.drawImage(bitmap, 0, Math.round(scrollY * dpr));
+      }
+    } finally {
+      await sendToTab(tab.id, { action: 'scroll_to', x: metrics.scrollX, y: metrics.scrollY });
     }
-    return true;
+  } else {
+    const bitmap = await captureBitmap(tab.windowId, captureOptions);
+    canvas = new OffscreenCanvas(bitmap.width, bitmap.height);
+    canvas.getContext('2d').drawImage(bitmap, 0, 0);
   }
-  return false;
+  return canvas;
 }
 
-function connectToMCP() {
-  if (ws && ws.readyState === WebSocket.OPEN) {
+async function takeScreenshot(tab, payload) {
+  const format = payload.format === 'jpeg' ? 'jpeg' : 'png';
+
+  // captureVisibleTab only sees the active tab of a window, so bring the
+  // target forward for the capture and give the user's tab back afterwards
+  let canvas;
+  if (tab.active) {
+    canvas = await captureCanvas(tab, payload);
+  } else {
+    const [previous] = await chrome.tabs.query({ active: true, windowId: tab.windowId });
+    await chrome.tabs.update(tab.id, { active: true });
+    try {
+      await new Promise((resolve) => setTimeout(resolve, 300));
+      canvas = await captureCanvas(tab, payload);
+    } finally {
+      if (previous) {
+        await chrome.tabs.update(previous.id, { active: true }).catch(() => {});
+      }
+    }
+  }
+
+  if (payload.maxWidth && canvas.width > payload.maxWidth) {
+    const scale = payload.maxWidth / canvas.width;
+    const scaled = new OffscreenCanvas(payload.maxWidth, Math.max(1, Math.round(canvas.height * scale)));
+    scaled.getContext('2d').drawImage(canvas, 0, 0, scaled.width, scaled.height);
+    canvas = scaled;
+  }
+
+  const mimeType = `image/${format}`;
+  const blob = await canvas.convertToBlob({ type: mimeType, quality: (payload.quality ?? 80) / 100 });
+  return { mimeType, data: await blobToBase64(blob), width: canvas.width, height: canvas.height };
+}
+
+async function connectToMCP() {
+  if (ws && (ws.readyState === WebSocket.OPEN || ws.readyState === WebSocket.CONNECTING)) {
     console.log('[BrowseHand] Already connected to MCP server');
     return;
   }
 
+  const { serverUrl } = await chrome.storage.local.get('serverUrl');
   console.log('[BrowseHand] Connecting to MCP server...');
-  ws = new WebSocket('ws://localhost:8765');
+  ws = new WebSocket(serverUrl || DEFAULT_SERVER_URL);
 
-  ws.onopen = () => {
+  ws.onopen = async () => {
     console.log('[BrowseHand] ✅ Connected to MCP server');
     if (reconnectInterval) {
       clearInterval(reconnectInterval);
       reconnectInterval = null;
     }
+
+    const { pairingToken } = await chrome.storage.local.get('pairingToken');
+    ws.send(JSON.stringify({
+      type: 'hello',
+      session: await getSessionName(),
+      version: chrome.runtime.getManifest().version,
+      token: pairingToken || '',
+      protocol: PROTOCOL_VERSION
+    }));
   };
 
   ws.onmessage = async (event) => {
@@ -58,161 +242,168 @@ function connectToMCP() {
     switch (message.type) {
       case 'hello':
         console.log('[BrowseHand] 🎉', message.message);
+        authenticated = true;
+        authError = null;
         break;
 
-      case 'ping':
-        ws.send(JSON.stringify({ 
-          type: 'pong', 
-          requestId: message.requestId,
-          success: true,
-          timestamp: Date.now() 
-        }));
-        console.log('[BrowseHand] Pong sent to MCP');
+      case 'auth_error':
+        console.error('[BrowseHand] 🔒 Pairing rejected:', message.message);
+        authError = message.message;
         break;
 
-      case 'read_content':
-        const tabs = await chrome.tabs.query({ active: true, currentWindow: true });
-        if (tabs[0]) {
-          chrome.tabs.sendMessage(tabs[0].id, {
-            action: 'read_content',
-            selector: message.payload?.selector || message.selector
-          }, (response) => {
-            if (handleExtensionError(message.requestId, 'read_content')) return;
-
-            ws.send(JSON.stringify({ 
-              type: 'content',
-              requestId: message.requestId,
-              success: true,
-              data: response.content 
-            }));
-          });
-        }
+      case 'protocol_error':
+        console.error('[BrowseHand] ⛔ Incompatible MCP server:', message.message);
+        protocolError = message.message;
         break;
 
-      case 'execute_script':
-        const activeTabs = await chrome.tabs.query({ active: true, currentWindow: true });
-        if (activeTabs[0]) {
-          chrome.tabs.sendMessage(activeTabs[0].id, {
-            action: 'execute_script',
-            code: message.payload?.code || message.code
-          }, (response) => {
-            if (handleExtensionError(message.requestId, 'execute_script')) return;
-
-            ws.send(JSON.stringify({ 
-              type: 'script_result',
-              requestId: message.requestId,
-              success: true,
-              result: response.result 
-            }));
+      case 'cancel': {
+        const tabId = inFlight.get(message.requestId);
+        if (tabId !== undefined) {
+          inFlight.delete(message.requestId);
+          chrome.tabs.sendMessage(tabId, { action: 'cancel', requestId: message.requestId }, () => {
+            // The tab may have navigated away; nothing left to abort then.
+            void chrome.runtime.lastError;
           });
+          console.log('[BrowseHand] Cancelled request', message.requestId);
         }
         break;
+      }
 
-      case 'scroll_page': {
-        const tabs = await chrome.tabs.query({ active: true, currentWindow: true });
-        if (tabs[0]) {
-          const payload = message.payload || message;
-          chrome.tabs.sendMessage(tabs[0].id, {
-            action: 'scroll_page',
-            direction: payload.direction,
-            amount: payload.amount
-          }, (response) => {
-            if (handleExtensionError(message.requestId, 'scroll_page')) return;
+      case 'ping': {
+        // Same query as getTargetTab, so the server queues tab-less requests
+        // in the lane of the tab they will act on
+        const [activeTab] = await chrome.tabs.query({ active: true, currentWindow: true });
+        reply(message, {
+          version: chrome.runtime.getManifest().version,
+          activeTab: activeTab ? serializeTab(activeTab) : null
+        });
+        console.log('[BrowseHand] Pong sent to MCP');
+        break;
+      }
 
-            ws.send(JSON.stringify({ type: 'scroll_result', requestId: message.requestId, success: true }));
+      case 'read_content':
+      case 'execute_script':
+      case 'fill_form':
+      case 'press_keys':
+      case 'extract_structured_data':
+      case 'detect_challenge':
+      case 'get_dom_snapshot': {
+        const tab = await getTargetTab(message);
+        if (tab) {
+          chrome.tabs.sendMessage(tab.id, { action: message.type, ...message.payload }, (response) => {
+            if (handleExtensionError(message, message.type)) return;
+            reply(message, response);
           });
         }
         break;
       }
 
-      case 'click_element': {
-        const tabs = await chrome.tabs.query({ active: true, currentWindow: true });
-        if (tabs[0]) {
-          const payload = message.payload || message;
-          chrome.tabs.sendMessage(tabs[0].id, {
-            action: 'click_element',
-            selector: payload.selector,
-            waitAfter: payload.waitAfter
+      // Long-running page work the server may cancel while it runs
+      case 'scroll_page':
+      case 'click_element':
+      case 'type_text':
+      case 'wait_for_element': {
+        const tab = await getTargetTab(message);
+        if (tab) {
+          inFlight.set(message.requestId, tab.id);
+          chrome.tabs.sendMessage(tab.id, {
+            action: message.type,
+            requestId: message.requestId,
+            ...message.payload
           }, (response) => {
-            if (handleExtensionError(message.requestId, 'click_element', 'click_result')) return;
-
-            ws.send(JSON.stringify({ type: 'click_result', requestId: message.requestId, success: response.success, error: response.error }));
+            inFlight.delete(message.requestId);
+            if (handleExtensionError(message, message.type)) return;
+            reply(message, response);
           });
         }
         break;
       }
 
-      case 'wait_for_element': {
-        const tabs = await chrome.tabs.query({ active: true, currentWindow: true });
-        if (tabs[0]) {
-          const payload = message.payload || message;
-          chrome.tabs.sendMessage(tabs[0].id, {
-            action: 'wait_for_element',
-            selector: payload.selector,
-            timeout: payload.timeout
-          }, (response) => {
-            if (handleExtensionError(message.requestId, 'wait_for_element', 'wait_result')) return;
-
-            ws.send(JSON.stringify({ type: 'wait_result', requestId: message.requestId, success: response.success }));
-          });
+      case 'get_current_url': {
+        const tab = await getTargetTab(message);
+        if (tab) {
+          reply(message, { url: tab.url });
         }
         break;
       }
 
-      case 'extract_structured_data': {
-        const tabs = await chrome.tabs.query({ active: true, currentWindow: true });
-        if (tabs[0]) {
-          const payload = message.payload || message;
-          chrome.tabs.sendMessage(tabs[0].id, {
-            action: 'extract_structured_data',
-            containerSelector: payload.containerSelector,
-            fields: payload.fields,
-            limit: payload.limit
-          }, (response) => {
-            if (handleExtensionError(message.requestId, 'extract_structured_data', 'extracted_data')) return;
+      case 'navigate_to': {
+        const tab = await getTargetTab(message);
+        if (tab) {
+          chrome.tabs.update(tab.id, { url: message.payload.url }, () => {
+            if (handleExtensionError(message, 'navigate_to')) return;
 
-            ws.send(JSON.stringify({ type: 'extracted_data', requestId: message.requestId, success: response.success, data: response.data, error: response.error }));
+            setTimeout(() => reply(message, {}), 2000);
           });
         }
         break;
       }
 
-      case 'get_current_url': {
-        const tabs = await chrome.tabs.query({ active: true, currentWindow: true });
-        if (tabs[0]) {
-          ws.send(JSON.stringify({ type: 'current_url', requestId: message.requestId, success: true, url: tabs[0].url }));
+      case 'take_screenshot': {
+        const tab = await getTargetTab(message);
+        if (tab) {
+          try {
+            reply(message, await takeScreenshot(tab, message.payload));
+          } catch (error) {
+            console.error('[BrowseHand] Screenshot failed:', error);
+            replyError(message, error.message, { code: error.code, selector: error.selector });
+          }
         }
         break;
       }
 
-      case 'navigate_to': {
-        const tabs = await chrome.tabs.query({ active: true, currentWindow: true });
-        if (tabs[0]) {
-          const payload = message.payload || message;
-          chrome.tabs.update(tabs[0].id, { url: payload.url }, () => {
-            if (handleExtensionError(message.requestId, 'navigate_to', 'navigation_result')) return;
-
-            setTimeout(() => {
-              ws.send(JSON.stringify({ type: 'navigation_result', requestId: message.requestId, success: true }));
-            }, 2000);
-          });
+      case 'list_tabs': {
+        const { windowId } = message.payload;
+        const tabs = await chrome.tabs.query(windowId != null ? { windowId } : {});
+        reply(message, { tabs: tabs.map(serializeTab) });
+        break;
+      }
+
+      case 'open_tab': {
+        const { url, active, windowId } = message.payload;
+        const options = { active: active !== false };
+        if (url) options.url = url;
+        if (windowId != null) options.windowId = windowId;
+        try {
+          const tab = await chrome.tabs.create(options);
+          reply(message, { tab: serializeTab(tab) });
+        } catch (error) {
+          replyError(message, error.message);
         }
         break;
       }
 
-      case 'get_dom_snapshot': {
-        const tabs = await chrome.tabs.query({ active: true, currentWindow: true });
-        if (tabs[0]) {
-          chrome.tabs.sendMessage(tabs[0].id, {
-            action: 'get_dom_snapshot'
-          }, (response) => {
-            if (handleExtensionError(message.requestId, 'get_dom_snapshot', 'dom_snapshot')) return;
+      case 'close_tab': {
+        const tab = await getTargetTab(message);
+        if (tab) {
+          try {
+            await chrome.tabs.remove(tab.id);
+            reply(message, {});
+          } catch (error) {
+            replyError(message, error.message);
+          }
+        }
+        break;
+      }
 
-            ws.send(JSON.stringify({ type: 'dom_snapshot', requestId: message.requestId, success: response.success, html: response.html }));
-          });
+      case 'activate_tab': {
+        const tab = await getTargetTab(message);
+        if (tab) {
+          try {
+            await chrome.tabs.update(tab.id, { active: true });
+            await chrome.windows.update(tab.windowId, { focused: true });
+            reply(message, {});
+          } catch (error) {
+            replyError(message, error.message);
+          }
         }
         break;
       }
+
+      default:
+        if (message.requestId) {
+          replyError(message, `Unsupported request type: ${message.type}`);
+        }
     }
   };
 
@@ -221,8 +412,18 @@ function connectToMCP() {
   };
 
   ws.onclose = () => {
-    console.log('[BrowseHand] ❌ Disconnected from MCP server. Reconnecting...');
     ws = null;
+    authenticated = false;
+
+    if (authError) {
+      console.log('[BrowseHand] ❌ Disconnected from MCP server. Set the pairing token in the popup to reconnect.');
+      return;
+    }
+    if (protocolError) {
+      console.log('[BrowseHand] ❌ Disconnected from MCP server. Update the extension or the server to reconnect.');
+      return;
+    }
+    console.log('[BrowseHand] ❌ Disconnected from MCP server. Reconnecting...');
     
     if (!reconnectInterval) {
       reconnectInterval = setInterval(() => {
@@ -242,16 +443,128 @@ chrome.runtime.onStartup.addListener(() => {
   connectToMCP();
 });
 
+chrome.tabs.onUpdated.addListener((tabId, changeInfo, tab) => {
+  if (changeInfo.url) {
+    sendEvent({ kind: 'navigation', tabId, url: changeInfo.url });
+  }
+  if (changeInfo.status === 'complete') {
+    sendEvent({ kind: 'load', tabId, url: tab.url, title: tab.title });
+  }
+});
+
+function sendDownloadEvent(item, state) {
+  sendEvent({
+    kind: 'download',
+    downloadId: item.id,
+    url: item.finalUrl || item.url,
+    filename: item.filename || undefined,
+    state: state || item.state
+  });
+}
+
+chrome.downloads.onCreated.addListener((item) => sendDownloadEvent(item));
+
+const MAX_PAGE_EVENT_MESSAGE_LENGTH = 2000;
+const CONSOLE_ERROR_SOURCES = ['console', 'exception', 'rejection'];
+const DIALOG_TYPES = ['alert', 'confirm', 'prompt'];
+
+// Builds a console_error or dialog event from what page-hooks.js reported.
+// Any script on the page can send the same message, so only these two kinds
+// and their expected fields get through; the URL and tab come from the sender.
+function pageEvent(detail, sender) {
+  let reported;
+  try {
+    reported = JSON.parse(detail);
+  } catch {
+    return null;
+  }
+  if (!reported || typeof reported !== 'object' || typeof reported.message !== 'string') {
+    return null;
+  }
+  const base = {
+    tabId: sender.tab.id,
+    url: sender.url || sender.tab.url,
+    message: reported.message.slice(0, MAX_PAGE_EVENT_MESSAGE_LENGTH)
+  };
+  if (reported.kind === 'console_error') {
+    const source = CONSOLE_ERROR_SOURCES.includes(reported.source) ? reported.source : undefined;
+    return { kind: 'console_error', ...base, source };
+  }
+  if (reported.kind === 'dialog' && DIALOG_TYPES.includes(reported.dialogType)) {
+    return { kind: 'dialog', ...base, dialogType: reported.dialogType };
+  }
+  return null;
+}
+
+chrome.downloads.onChanged.addListener((delta) => {
+  if (!delta.state) return;
+  chrome.downloads.search({ id: delta.id }).then(([item]) => {
+    if (item) sendDownloadEvent(item, delta.state.current);
+  });
+});
+
 chrome.runtime.onMessage.addListener((request, sender, sendResponse) => {
+  // Console errors and dialogs reported by page-hooks.js through content.js
+  if (request.action === 'page_event') {
+    const event = sender.tab && typeof request.detail === 'string' ? pageEvent(request.detail, sender) : null;
+    if (event) {
+      sendEvent(event);
+    }
+    return false;
+  }
+
   if (request.action === 'reconnect') {
+    protocolError = null;
     connectToMCP();
     sendResponse({ success: true });
     return true;
   }
   
   if (request.action === 'getStatus') {
-    const connected = ws && ws.readyState === WebSocket.OPEN;
-    sendResponse({ connected });
+    const connected = Boolean(ws && ws.readyState === WebSocket.OPEN && authenticated);
+    Promise.all([getSessionName(), chrome.storage.local.get('serverUrl')]).then(([sessionName, { serverUrl }]) =>
+      sendResponse({ connected, sessionName, authError, protocolError, serverUrl: serverUrl || DEFAULT_SERVER_URL })
+    );
+    return true;
+  }
+
+  if (request.action === 'setServerUrl') {
+    chrome.storage.local.set({ serverUrl: request.serverUrl }).then(() => {
+      authError = null;
+      protocolError = null;
+      if (ws) {
+        ws.close();
+      } else {
+        connectToMCP();
+      }
+      sendResponse({ success: true });
+    });
+    return true;
+  }
+
+  if (request.action === 'setPairingToken') {
+    chrome.storage.local.set({ pairingToken: request.pairingToken }).then(() => {
+      authError = null;
+      if (ws) {
+        ws.close();
+      } else {
+        connectToMCP();
+      }
+      sendResponse({ success: true });
+    });
+    return true;
+  }
+
+  if (request.action === 'setSessionName') {
+    chrome.storage.local.set({ sessionName: request.sessionName }).then(() => {
+      // Re-handshake so the server registers the new name
+      if (ws) {
+        ws.close();
+      } else {
+        connectToMCP();
+      }
+      sendResponse({ success: true });
+    });
     return true;
   }
 });
@@ -1,5 +1,404 @@
 console.log('[BrowseHand Content] Script loaded on:', window.location.href);
 
+// Relay console errors and dialogs seen by page-hooks.js (which runs in the
+// page's world) to the background worker, then tell it we are listening.
+// Page scripts can fire this event too; the worker checks what it gets.
+document.addEventListener('browsehand:event', (event) => {
+  if (typeof event.detail !== 'string') return;
+  try {
+    chrome.runtime.sendMessage({ action: 'page_event', detail: event.detail }).catch(() => {});
+  } catch (error) {
+    // The extension was reloaded; this content script is orphaned.
+  }
+});
+document.dispatchEvent(new CustomEvent('browsehand:ready'));
+
+// Sets an input's value through the native setter so frameworks that track
+// the value property (React, Vue) notice the change.
+function setNativeValue(element, value) {
+  const proto = Object.getPrototypeOf(element);
+  const descriptor = Object.getOwnPropertyDescriptor(proto, 'value');
+  if (descriptor && descriptor.set) {
+    descriptor.set.call(element, value);
+  } else {
+    element.value = value;
+  }
+}
+
+function dispatchInputEvents(element) {
+  element.dispatchEvent(new Event('input', { bubbles: true }));
+  element.dispatchEvent(new Event('change', { bubbles: true }));
+}
+
+function base64ToFile(file) {
+  const binary = atob(file.data);
+  const bytes = new Uint8Array(binary.length);
+  for (let i = 0; i < binary.length; i++) {
+    bytes[i] = binary.charCodeAt(i);
+  }
+  return new File([bytes], file.name, { type: file.mimeType });
+}
+
+function fillField(field) {
+  const { selector, kind, value } = field;
+  const element = document.querySelector(selector);
+  if (!element) {
+    return { selector, success: false, code: 'selector_not_found', error: `Element not found: ${selector}` };
+  }
+
+  element.focus();
+
+  switch (kind) {
+    case 'select': {
+      const wanted = String(value);
+      const option = Array.from(element.options || []).find(
+        (o) => o.value === wanted || o.text.trim() === wanted
+      );
+      if (!option) {
+        return { selector, success: false, error: `Option not found: ${wanted}` };
+      }
+      setNativeValue(element, option.value);
+      dispatchInputEvents(element);
+      break;
+    }
+
+    case 'checkbox': {
+      const wanted = value === true || value === 'true' || value === 1 || value === 'on';
+      if (element.checked !== wanted) {
+        element.click();
+      }
+      break;
+    }
+
+    case 'radio': {
+      const wanted = String(value);
+      const candidates = element.name
+        ? document.querySelectorAll(`input[type="radio"][name="${CSS.escape(element.name)}"]`)
+        : document.querySelectorAll(selector);
+      const radio = Array.from(candidates).find((r) => r.value === wanted);
+      if (!radio) {
+        return { selector, success: false, error: `Radio option not found: ${wanted}` };
+      }
+      if (!radio.checked) {
+        radio.click();
+      }
+      break;
+    }
+
+    case 'file': {
+      if (!field.file) {
+        return { selector, success: false, error: 'No file contents provided' };
+      }
+      const transfer = new DataTransfer();
+      transfer.items.add(base64ToFile(field.file));
+      element.files = transfer.files;
+      dispatchInputEvents(element);
+      break;
+    }
+
+    default: {
+      // text and date inputs
+      setNativeValue(element, value == null ? '' : String(value));
+      dispatchInputEvents(element);
+    }
+  }
+
+  element.blur();
+  return { selector, success: true };
+}
+
+const KEY_CODES = {
+  Enter: 'Enter', Tab: 'Tab', Escape: 'Escape', Backspace: 'Backspace', Delete: 'Delete',
+  ArrowUp: 'ArrowUp', ArrowDown: 'ArrowDown', ArrowLeft: 'ArrowLeft', ArrowRight: 'ArrowRight',
+  Home: 'Home', End: 'End', PageUp: 'PageUp', PageDown: 'PageDown', ' ': 'Space'
+};
+
+const KEY_ALIASES = {
+  esc: 'Escape', return: 'Enter', space: ' ', del: 'Delete',
+  up: 'ArrowUp', down: 'ArrowDown', left: 'ArrowLeft', right: 'ArrowRight'
+};
+
+function sleep(ms) {
+  return new Promise((resolve) => setTimeout(resolve, ms));
+}
+
+// Aborts for requests still working in the page, by requestId. The server
+// sends `cancel` when the MCP client gave up or the request timed out.
+const cancelHandlers = new Map();
+
+function onCancel(requestId, abort) {
+  if (requestId) {
+    cancelHandlers.set(requestId, abort);
+  }
+}
+
+function settled(requestId) {
+  cancelHandlers.delete(requestId);
+}
+
+// For loops that check between steps whether they should stop.
+function cancellation(requestId) {
+  const token = { cancelled: false };
+  onCancel(requestId, () => { token.cancelled = true; });
+  return token;
+}
+
+const CANCELLED = { success: false, code: 'cancelled', error: 'Request cancelled' };
+
+function keyCodeFor(key) {
+  if (KEY_CODES[key]) return KEY_CODES[key];
+  if (/^[a-z]$/i.test(key)) return `Key${key.toUpperCase()}`;
+  if (/^[0-9]$/.test(key)) return `Digit${key}`;
+  return '';
+}
+
+// Parses a chord like "Control+Shift+A" into a key and modifier flags.
+function parseChord(chord) {
+  const parts = chord.split('+').map((p) => p.trim()).filter(Boolean);
+  const modifiers = { ctrlKey: false, shiftKey: false, altKey: false, metaKey: false };
+  let key = parts.length ? parts[parts.length - 1] : chord;
+  for (const part of parts.slice(0, -1)) {
+    const name = part.toLowerCase();
+    if (name === 'ctrl' || name === 'control') modifiers.ctrlKey = true;
+    else if (name === 'shift') modifiers.shiftKey = true;
+    else if (name === 'alt' || name === 'option') modifiers.altKey = true;
+    else if (name === 'meta' || name === 'cmd' || name === 'command') modifiers.metaKey = true;
+    else throw new Error(`Unknown modifier: ${part}`);
+  }
+  key = KEY_ALIASES[key.toLowerCase()] || key;
+  if (key.length === 1 && modifiers.shiftKey) key = key.toUpperCase();
+  return { key, modifiers };
+}
+
+function dispatchKey(element, type, key, modifiers) {
+  const event = new KeyboardEvent(type, {
+    key,
+    code: keyCodeFor(key),
+    bubbles: true,
+    cancelable: true,
+    composed: true,
+    ...modifiers
+  });
+  return element.dispatchEvent(event);
+}
+
+function isEditable(element) {
+  return element.isContentEditable ||
+    element instanceof HTMLTextAreaElement ||
+    (element instanceof HTMLInputElement && !['checkbox', 'radio', 'button', 'submit', 'file'].includes(element.type));
+}
+
+function insertText(element, text) {
+  // execCommand goes through the browser's editing pipeline, which fires
+  // beforeinput/input the same way real typing does.
+  if (document.execCommand && document.execCommand('insertText', false, text)) {
+    return;
+  }
+  if (element.isContentEditable) {
+    element.textContent += text;
+  } else {
+    setNativeValue(element, element.value + text);
+  }
+  element.dispatchEvent(new InputEvent('input', { bubbles: true, inputType: 'insertText', data: text }));
+}
+
+function focusTarget(selector) {
+  const element = selector ? document.querySelector(selector) : document.activeElement;
+  if (!element) {
+    return null;
+  }
+  element.focus();
+  return element;
+}
+
+function focusableElements() {
+  return Array.from(document.querySelectorAll(
+    'a[href], button, input, select, textarea, [tabindex]:not([tabindex="-1"]), [contenteditable="true"]'
+  )).filter((el) => !el.disabled && el.offsetParent !== null);
+}
+
+// Default actions the browser would perform for a key, since synthetic
+// keyboard events do not trigger them on their own.
+function performDefaultAction(element, key, modifiers) {
+  if ((modifiers.ctrlKey || modifiers.metaKey) && key.toLowerCase() === 'a') {
+    if (typeof element.select === 'function') element.select();
+    else document.execCommand('selectAll');
+    return;
+  }
+
+  switch (key) {
+    case 'Enter':
+      if (element instanceof HTMLTextAreaElement || element.isContentEditable) {
+        insertText(element, '\n');
+      } else if (element.form) {
+        element.form.requestSubmit();
+      } else if (element instanceof HTMLButtonElement || element instanceof HTMLAnchorElement) {
+        element.click();
+      }
+      break;
+    case 'Tab': {
+      const focusable = focusableElements();
+      const index = focusable.indexOf(element);
+      const next = focusable[(index + (modifiers.shiftKey ? -1 : 1) + focusable.length) % focusable.length];
+      if (next) next.focus();
+      break;
+    }
+    case 'Backspace':
+    case 'Delete':
+      if (isEditable(element)) {
+        if (!document.execCommand(key === 'Backspace' ? 'delete' : 'forwardDelete')) {
+          setNativeValue(element, element.value.slice(0, -1));
+          dispatchInputEvents(element);
+        }
+      }
+      break;
+    default:
+      if (key.length === 1 && !modifiers.ctrlKey && !modifiers.metaKey && isEditable(element)) {
+        insertText(element, key);
+      }
+  }
+}
+
+function pressKey(element, key, modifiers) {
+  const proceed = dispatchKey(element, 'keydown', key, modifiers);
+  if (proceed && key.length === 1) {
+    dispatchKey(element, 'keypress', key, modifiers);
+  }
+  if (proceed) {
+    performDefaultAction(element, key, modifiers);
+  }
+  dispatchKey(element, 'keyup', key, modifiers);
+}
+
+async function typeText(request) {
+  const element = focusTarget(request.selector);
+  if (!element) {
+    return { success: false, code: 'selector_not_found', selector: request.selector, error: `Element not found: ${request.selector}` };
+  }
+  if (!isEditable(element)) {
+    return { success: false, error: `Element is not editable: ${request.selector || element.tagName.toLowerCase()}` };
+  }
+  if (request.clear) {
+    if (element.isContentEditable) {
+      element.textContent = '';
+    } else {
+      setNativeValue(element, '');
+    }
+    dispatchInputEvents(element);
+  }
+  const noModifiers = { ctrlKey: false, shiftKey: false, altKey: false, metaKey: false };
+  const token = cancellation(request.requestId);
+  for (const ch of request.text || '') {
+    if (token.cancelled) {
+      return CANCELLED;
+    }
+    pressKey(element, ch === '\n' ? 'Enter' : ch, noModifiers);
+    if (request.delay) {
+      await sleep(request.delay);
+    }
+  }
+  element.dispatchEvent(new Event('change', { bubbles: true }));
+  return { success: true };
+}
+
+function scrollPosition(container, rounds) {
+  const maxTop = container.scrollHeight - container.clientHeight;
+  return {
+    success: true,
+    scrollTop: Math.round(container.scrollTop),
+    scrollHeight: container.scrollHeight,
+    clientHeight: container.clientHeight,
+    atEnd: container.scrollTop >= maxTop - 2,
+    rounds
+  };
+}
+
+// Scrolls the page or the `selector` container. `intoView` brings an element
+// inside it into view instead; `untilStable` keeps jumping to the bottom until
+// lazy loading stops adding content.
+async function scrollPage(request) {
+  const { direction, amount, selector, intoView, untilStable } = request;
+  let container = document.scrollingElement || document.documentElement;
+
+  if (selector) {
+    container = document.querySelector(selector);
+    if (!container) {
+      return { success: false, code: 'selector_not_found', selector, error: `Scroll target not found: ${selector}` };
+    }
+  }
+
+  if (intoView) {
+    const element = (selector ? container : document).querySelector(intoView);
+    if (!element) {
+      return { success: false, code: 'selector_not_found', selector: intoView, error: `Element not found: ${intoView}` };
+    }
+    element.scrollIntoView({ block: 'center', inline: 'nearest' });
+    return scrollPosition(container, 0);
+  }
+
+  if (untilStable) {
+    const settleMs = request.settleMs || 1000;
+    const maxRounds = request.maxRounds || 20;
+    const token = cancellation(request.requestId);
+    let rounds = 0;
+    while (rounds < maxRounds) {
+      if (token.cancelled) {
+        return CANCELLED;
+      }
+      const before = container.scrollHeight;
+      container.scrollTo(0, container.scrollHeight);
+      await sleep(settleMs);
+      rounds++;
+      if (container.scrollHeight <= before) break;
+    }
+    return scrollPosition(container, rounds);
+  }
+
+  if (direction === 'bottom') {
+    container.scrollTo(0, container.scrollHeight);
+  } else if (direction === 'top') {
+    container.scrollTo(0, 0);
+  } else if (direction === 'up') {
+    container.scrollBy(0, -amount);
+  } else {
+    // default down
+    container.scrollBy(0, amount);
+  }
+  return scrollPosition(container, 1);
+}
+
+function isVisible(element) {
+  const rect = element.getBoundingClientRect();
+  const style = window.getComputedStyle(element);
+  return rect.width > 0 && rect.height > 0 && style.visibility !== 'hidden' && style.display !== 'none';
+}
+
+// Looks for captcha widgets and bot-wall interstitials that need a human.
+function detectChallenge() {
+  const visible = (selector) => Array.from(document.querySelectorAll(selector)).some(isVisible);
+
+  // The invisible reCAPTCHA v3 badge is not a challenge; only the checkbox
+  // anchor and the image challenge frame are
+  if (visible('iframe[src*="recaptcha/api2/anchor"], iframe[src*="recaptcha/api2/bframe"], iframe[src*="recaptcha/enterprise/anchor"]')) {
+    return { detected: true, kind: 'recaptcha', detail: 'reCAPTCHA widget on page' };
+  }
+  if (visible('iframe[src*="hcaptcha.com"], .h-captcha')) {
+    return { detected: true, kind: 'hcaptcha', detail: 'hCaptcha widget on page' };
+  }
+  if (document.querySelector('#challenge-form, #cf-challenge-running, #challenge-stage, .cf-browser-verification') ||
+      /^just a moment/i.test(document.title.trim())) {
+    return { detected: true, kind: 'cloudflare', detail: 'Cloudflare browser check' };
+  }
+  if (visible('iframe[src*="challenges.cloudflare.com"]')) {
+    return { detected: true, kind: 'turnstile', detail: 'Cloudflare Turnstile widget on page' };
+  }
+  const text = (document.body && document.body.innerText || '').slice(0, 5000).toLowerCase();
+  if (text.includes('unusual traffic') || text.includes('are you a robot') || location.pathname.startsWith('/sorry/')) {
+    return { detected: true, kind: 'unusual_traffic', detail: 'Bot-detection page ("unusual traffic")' };
+  }
+  return { detected: false };
+}
+
 chrome.runtime.onMessage.addListener((request, sender, sendResponse) => {
   console.log('[BrowseHand Content] Received message:', request);
 
@@ -11,15 +410,16 @@ chrome.runtime.onMessage.addListener((request, sender, sendResponse) => {
       if (!element) {
         sendResponse({ 
           success: false, 
+          code: 'selector_not_found',
+          selector: selector,
           error: `Element not found: ${selector}` 
         });
         return true;
       }
 
-      const content = element.innerText || element.textContent;
       sendResponse({ 
         success: true, 
-        content: content 
+        data: element.innerText || element.textContent
       });
     } catch (error) {
       sendResponse({ 
@@ -41,46 +441,28 @@ chrome.runtime.onMessage.addListener((request, sender, sendResponse) => {
     } catch (error) {
       sendResponse({ 
         success: false, 
+        code: 'script_exception',
         error: error.message 
       });
     }
     return true;
   }
 
-  if (request.action === 'scroll_page') {
-    try {
-      const { direction, amount, selector } = request;
-      let target = window;
-      let isElement = false;
-
-      if (selector) {
-        const element = document.querySelector(selector);
-        if (!element) {
-          throw new Error(`Scroll target not found: ${selector}`);
-        }
-        target = element;
-        isElement = true;
-      }
-      
-      if (direction === 'bottom') {
-        if (isElement) {
-          target.scrollTo(0, target.scrollHeight);
-        } else {
-          window.scrollTo(0, document.body.scrollHeight);
-        }
-      } else if (direction === 'top') {
-        target.scrollTo(0, 0);
-      } else if (direction === 'up') {
-        target.scrollBy(0, -amount);
-      } else {
-        // default down
-        target.scrollBy(0, amount);
-      }
-      
-      sendResponse({ success: true });
-    } catch (error) {
-      sendResponse({ success: false, error: error.message });
+  if (request.action === 'cancel') {
+    const abort = cancelHandlers.get(request.requestId);
+    settled(request.requestId);
+    if (abort) {
+      abort();
     }
+    sendResponse({ success: true, cancelled: Boolean(abort) });
+    return true;
+  }
+
+  if (request.action === 'scroll_page') {
+    scrollPage(request)
+      .finally(() => settled(request.requestId))
+      .then(sendResponse)
+      .catch((error) => sendResponse({ success: false, error: error.message }));
     return true;
   }
 
@@ -92,14 +474,19 @@ chrome.runtime.onMessage.addListener((request, sender, sendResponse) => {
         
         // Wait after click (optional)
         if (request.waitAfter) {
-          setTimeout(() => {
+          const timer = setTimeout(() => {
+            settled(request.requestId);
             sendResponse({ success: true });
           }, request.waitAfter);
+          onCancel(request.requestId, () => {
+            clearTimeout(timer);
+            sendResponse(CANCELLED);
+          });
         } else {
           sendResponse({ success: true });
         }
       } else {
-        sendResponse({ success: false, error: 'Element not found' });
+        sendResponse({ success: false, code: 'selector_not_found', selector: request.selector, error: 'Element not found' });
       }
     } catch (error) {
       sendResponse({ success: false, error: error.message });
@@ -107,6 +494,105 @@ chrome.runtime.onMessage.addListener((request, sender, sendResponse) => {
     return true; // Return true for async response
   }
 
+  if (request.action === 'fill_form') {
+    try {
+      const results = (request.fields || []).map((field) => {
+        try {
+          return fillField(field);
+        } catch (error) {
+          return { selector: field.selector, success: false, error: error.message };
+        }
+      });
+
+      if (request.submitSelector) {
+        const submit = document.querySelector(request.submitSelector);
+        if (submit) {
+          submit.click();
+          results.push({ selector: request.submitSelector, submit: true, success: true });
+        } else {
+          results.push({ selector: request.submitSelector, submit: true, success: false, error: 'Element not found' });
+        }
+      }
+
+      sendResponse({ success: true, results });
+    } catch (error) {
+      sendResponse({ success: false, error: error.message });
+    }
+    return true;
+  }
+
+  if (request.action === 'type_text') {
+    typeText(request)
+      .finally(() => settled(request.requestId))
+      .then(sendResponse)
+      .catch((error) => sendResponse({ success: false, error: error.message }));
+    return true;
+  }
+
+  if (request.action === 'press_keys') {
+    try {
+      const element = focusTarget(request.selector);
+      if (!element) {
+        sendResponse({ success: false, code: 'selector_not_found', selector: request.selector, error: `Element not found: ${request.selector}` });
+        return true;
+      }
+      for (const chord of request.keys || []) {
+        const { key, modifiers } = parseChord(chord);
+        // Focus may move (e.g. Tab), so each key goes to the current focus
+        pressKey(document.activeElement || element, key, modifiers);
+      }
+      sendResponse({ success: true });
+    } catch (error) {
+      sendResponse({ success: false, error: error.message });
+    }
+    return true;
+  }
+
+  if (request.action === 'get_element_rect') {
+    const element = document.querySelector(request.selector);
+    if (!element) {
+      sendResponse({ success: false, code: 'selector_not_found', selector: request.selector, error: `Element not found: ${request.selector}` });
+      return true;
+    }
+    element.scrollIntoView({ block: 'start', inline: 'nearest' });
+    const rect = element.getBoundingClientRect();
+    sendResponse({
+      success: true,
+      rect: { x: rect.left, y: rect.top, width: rect.width, height: rect.height },
+      dpr: window.devicePixelRatio
+    });
+    return true;
+  }
+
+  if (request.action === 'get_page_metrics') {
+    const root = document.scrollingElement || document.documentElement;
+    sendResponse({
+      success: true,
+      scrollHeight: root.scrollHeight,
+      viewportWidth: window.innerWidth,
+      viewportHeight: window.innerHeight,
+      scrollX: window.scrollX,
+      scrollY: window.scrollY,
+      dpr: window.devicePixelRatio
+    });
+    return true;
+  }
+
+  if (request.action === 'scroll_to') {
+    window.scrollTo(request.x || 0, request.y || 0);
+    sendResponse({ success: true, scrollY: window.scrollY });
+    return true;
+  }
+
+  if (request.action === 'detect_challenge') {
+    try {
+      sendResponse({ success: true, ...detectChallenge() });
+    } catch (error) {
+      sendResponse({ success: false, error: error.message });
+    }
+    return true;
+  }
+
   if (request.action === 'wait_for_element') {
     const { selector, timeout } = request;
     
@@ -119,6 +605,8 @@ chrome.runtime.onMessage.addListener((request, sender, sendResponse) => {
       const element = document.querySelector(selector);
       if (element) {
         obs.disconnect();
+        clearTimeout(timer);
+        settled(request.requestId);
         sendResponse({ success: true });
       }
     });
@@ -128,10 +616,18 @@ chrome.runtime.onMessage.addListener((request, sender, sendResponse) => {
       subtree: true
     });
 
-    setTimeout(() => {
+    const timer = setTimeout(() => {
       observer.disconnect();
+      settled(request.requestId);
+      sendResponse({ success: false, code: 'selector_not_found', selector, error: `Element not found: ${selector}` });
     }, timeout);
 
+    onCancel(request.requestId, () => {
+      observer.disconnect();
+      clearTimeout(timer);
+      sendResponse(CANCELLED);
+    });
+
     return true;
   }
 
@@ -0,0 +1,87 @@
+// Runs in the page's own JavaScript world (content.js cannot see the page's
+// console or dialogs) and reports console errors, uncaught exceptions and
+// dialogs to content.js, which forwards them to the MCP server as events.
+(() => {
+  const MAX_MESSAGE_LENGTH = 2000;
+  // content.js loads after the page; hold events until it says it listens.
+  let ready = false;
+  const queued = [];
+
+  function emit(event) {
+    const detail = JSON.stringify(event);
+    if (ready) {
+      document.dispatchEvent(new CustomEvent('browsehand:event', { detail }));
+    } else if (queued.length < 100) {
+      queued.push(detail);
+    }
+  }
+
+  document.addEventListener('browsehand:ready', () => {
+    ready = true;
+    for (const detail of queued.splice(0)) {
+      document.dispatchEvent(new CustomEvent('browsehand:event', { detail }));
+    }
+  });
+
+  // Pages can tell a patched function by its source text, its name or a
+  // prototype property native ones lack, so the wrappers below are methods
+  // (no prototype) with the original's name and length, and toString gives
+  // the original's source for each of them, itself included.
+  const disguised = new WeakMap();
+  const nativeToString = Function.prototype.toString;
+
+  function disguise(wrapper, original) {
+    for (const key of ['name', 'length']) {
+      Object.defineProperty(wrapper, key, { value: original[key], configurable: true });
+    }
+    disguised.set(wrapper, original);
+    return wrapper;
+  }
+
+  Function.prototype.toString = disguise({
+    toString() {
+      return nativeToString.call(disguised.get(this) || this);
+    },
+  }.toString, nativeToString);
+
+  function describe(value) {
+    if (value instanceof Error) return value.stack || `${value.name}: ${value.message}`;
+    if (typeof value === 'string') return value;
+    try {
+      return JSON.stringify(value);
+    } catch {
+      return String(value);
+    }
+  }
+
+  function consoleError(source, values) {
+    emit({ kind: 'console_error', source, message: values.map(describe).join(' ').slice(0, MAX_MESSAGE_LENGTH) });
+  }
+
+  const originalError = console.error;
+  console.error = disguise({
+    error(...args) {
+      consoleError('console', args);
+      return originalError.apply(this, args);
+    },
+  }.error, originalError);
+
+  window.addEventListener('error', (event) => {
+    consoleError('exception', [event.error || event.message]);
+  });
+
+  window.addEventListener('unhandledrejection', (event) => {
+    consoleError('rejection', [event.reason]);
+  });
+
+  // Reported before the dialog opens: it blocks the page until answered.
+  for (const dialogType of ['alert', 'confirm', 'prompt']) {
+    const original = window[dialogType];
+    window[dialogType] = disguise({
+      [dialogType](message, ...rest) {
+        emit({ kind: 'dialog', dialogType, message: message === undefined ? '' : String(message) });
+        return original.call(this, message, ...rest);
+      },
+    }[dialogType], original);
+  }
+})();
@@ -6,11 +6,36 @@ document.getElementById('reconnect').addEventListener('click', () => {
   });
 });
 
-function updateStatus(connected) {
+document.getElementById('save-session').addEventListener('click', () => {
+  const sessionName = document.getElementById('session-name').value.trim();
+  if (!sessionName) return;
+  chrome.runtime.sendMessage({ action: 'setSessionName', sessionName });
+});
+
+document.getElementById('save-url').addEventListener('click', () => {
+  const serverUrl = document.getElementById('server-url').value.trim();
+  if (!serverUrl) return;
+  chrome.runtime.sendMessage({ action: 'setServerUrl', serverUrl });
+});
+
+document.getElementById('save-token').addEventListener('click', () => {
+  const pairingToken = document.getElementById('pairing-token').value.trim();
+  if (!pairingToken) return;
+  chrome.runtime.sendMessage({ action: 'setPairingToken', pairingToken });
+  document.getElementById('pairing-token').value = '';
+});
+
+function updateStatus(connected, authError, protocolError) {
   const statusEl = document.getElementById('status');
   if (connected) {
     statusEl.className = 'status connected';
     statusEl.textContent = '🟢 Connected to MCP Server';
+  } else if (protocolError) {
+    statusEl.className = 'status disconnected';
+    statusEl.textContent = `⛔ ${protocolError}`;
+  } else if (authError) {
+    statusEl.className = 'status disconnected';
+    statusEl.textContent = '🔒 Pairing token rejected by MCP Server';
   } else {
     statusEl.className = 'status disconnected';
     statusEl.textContent = '🔴 Not Connected to MCP Server';
@@ -18,7 +43,13 @@ function updateStatus(connected) {
 }
 
 chrome.runtime.sendMessage({ action: 'getStatus' }, (response) => {
-  if (response && response.connected) {
-    updateStatus(true);
+  if (response) {
+    updateStatus(response.connected, response.authError, response.protocolError);
+  }
+  if (response && response.serverUrl) {
+    document.getElementById('server-url').value = response.serverUrl;
+  }
+  if (response && response.sessionName) {
+    document.getElementById('session-name').value = response.sessionName;
   }
 });
@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use tracing::info;
+
+use crate::config;
+
+/// Id of the bundled extension, fixed by the `key` in its manifest.
+pub const EXTENSION_ID: &str = "lpgdlbimoajlodfcmfdniigjmlkijaad";
+
+pub fn token_path() -> PathBuf {
+    config::config_dir().join("token")
+}
+
+/// Shared secret the extension must present in its hello, plus the extension
+/// ids whose `Origin` may open a bridge connection at all.
+pub struct Pairing {
+    token: String,
+    extension_ids: Vec<String>,
+}
+
+impl Pairing {
+    /// Reads the pairing token, generating and storing one on first run.
+    pub fn load_or_create(extension_ids: Vec<String>) -> Result<Self> {
+        let path = token_path();
+        let token = match std::fs::read_to_string(&path) {
+            Ok(content) if !content.trim().is_empty() => content.trim().to_string(),
+            _ => {
+                let token = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+                config::write_private(&path, &token)
+                    .with_context(|| format!("failed to write pairing token to {}", path.display()))?;
+                info!("Generated pairing token in {}; paste it into the extension popup", path.display());
+                token
+            }
+        };
+
+        Ok(Self { token, extension_ids })
+    }
+
+    /// Browsers always send `Origin` on WebSocket upgrades, so this keeps web
+    /// pages from reaching the bridge. Non-browser clients such as the CLI send
+    /// none and can forge it anyway; the token is what stops those.
+    pub fn allows_origin(&self, origin: Option<&str>) -> bool {
+        let Some(origin) = origin else {
+            return true;
+        };
+        origin
+            .strip_prefix("chrome-extension://")
+            .is_some_and(|id| self.extension_ids.iter().any(|allowed| allowed == id))
+    }
+
+    pub fn verify(&self, token: Option<&str>) -> bool {
+        let Some(token) = token else {
+            return false;
+        };
+        // Constant-time comparison so the token cannot be guessed byte by byte.
+        token.len() == self.token.len()
+            && token
+                .bytes()
+                .zip(self.token.bytes())
+                .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+}
@@ -0,0 +1,68 @@
+use rmcp::model::{CallToolResult, Content};
+use serde_json::Value;
+
+use crate::error::Result;
+use crate::protocol::DetectChallenge;
+use crate::websocket::{Caller, ExtensionBridge, Target};
+
+/// A captcha or bot-wall page that needs a human before automation can go on.
+#[derive(Debug, Clone)]
+pub struct Challenge {
+    pub kind: String,
+    pub detail: String,
+}
+
+impl Challenge {
+    pub fn to_json(&self) -> Value {
+        serde_json::json!({
+            "kind": self.kind,
+            "detail": self.detail,
+        })
+    }
+}
+
+/// Asks the extension whether the target tab shows a challenge page. Fails
+/// when the tab cannot be inspected (no content script yet, chrome:// pages,
+/// a closed tab, ...); callers decide whether that matters.
+pub async fn detect(bridge: &ExtensionBridge, caller: &Caller, target: &Target) -> Result<Option<Challenge>> {
+    let reply = bridge.send_and_wait(caller, DetectChallenge {}, target).await?;
+
+    if !reply.detected {
+        return Ok(None);
+    }
+
+    Ok(Some(Challenge {
+        kind: reply.kind.unwrap_or_else(|| "unknown".to_string()),
+        detail: reply.detail.unwrap_or_default(),
+    }))
+}
+
+/// Runs challenge detection after a page-changing action and, if a challenge
+/// is showing, marks the result with a `human_required` status. The action
+/// itself succeeded, so a page that cannot be checked leaves it unmarked.
+pub async fn annotate(
+    bridge: &ExtensionBridge,
+    caller: &Caller,
+    target: &Target,
+    mut result: CallToolResult,
+) -> CallToolResult {
+    if let Ok(Some(challenge)) = detect(bridge, caller, target).await {
+        result.content.push(Content::text(format!(
+            "⚠️ {} challenge detected ({}). A human must solve it in the browser; call wait_for_human to continue once it is cleared.",
+            challenge.kind, challenge.detail
+        )));
+        match result.structured_content.as_mut().and_then(Value::as_object_mut) {
+            Some(obj) => {
+                obj.insert("status".into(), serde_json::json!("human_required"));
+                obj.insert("challenge".into(), challenge.to_json());
+            }
+            None => {
+                result.structured_content = Some(serde_json::json!({
+                    "status": "human_required",
+                    "challenge": challenge.to_json(),
+                }));
+            }
+        }
+    }
+    result
+}
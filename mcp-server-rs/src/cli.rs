@@ -0,0 +1,243 @@
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::process::ExitCode;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{client_async, WebSocketStream};
+
+use crate::auth::{self, Pairing};
+use crate::config::{self, Config, ConfigArgs};
+use crate::protocol::PROTOCOL_VERSION;
+use crate::tls;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "AI-powered browser automation MCP server")]
+pub struct Cli {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Serve MCP over stdio and run the extension bridge (the default)
+    Serve,
+    /// Show whether browsers are connected to a running server
+    /// (exit code 1 if none are, 2 if no server is reachable)
+    Status,
+    /// Call one tool on a running server and print its result
+    Call {
+        /// Tool name, e.g. navigate_to
+        tool: String,
+        /// Tool arguments as a JSON object
+        #[arg(long, default_value = "{}")]
+        args: String,
+        /// Print the whole tool result as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Manage the config file
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Print the JSON Schema of the messages between server and extension
+    Schema,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Write a config file with the current settings and create the pairing token
+    Init {
+        /// Overwrite an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+/// MCP client over the bridge socket of a running server, authenticated with
+/// the pairing token as a `control` client.
+struct ControlClient {
+    ws: WebSocketStream<Box<dyn Io>>,
+    next_id: u64,
+}
+
+impl ControlClient {
+    async fn connect(config: &Config) -> Result<Self> {
+        let addr = config.client_addr();
+        let tcp = TcpStream::connect(addr)
+            .await
+            .with_context(|| format!("no BrowseHand server is listening on {}", addr))?;
+        let stream: Box<dyn Io> = if config.tls {
+            Box::new(tls::connect(config, addr, tcp).await?)
+        } else {
+            Box::new(tcp)
+        };
+        let (ws, _) = client_async(format!("{}/", config.client_url()), stream)
+            .await
+            .context("WebSocket handshake with the server failed")?;
+
+        let token = std::fs::read_to_string(auth::token_path())
+            .context("cannot read the pairing token; start the server once or run `browsehand config init`")?;
+        let mut client = Self { ws, next_id: 0 };
+        client
+            .send(serde_json::json!({
+                "type": "hello",
+                "role": "control",
+                "token": token.trim(),
+                "version": env!("CARGO_PKG_VERSION"),
+                "protocol": PROTOCOL_VERSION
+            }))
+            .await?;
+        let reply = client.next_message().await?;
+        match reply["type"].as_str() {
+            Some("hello") => {}
+            Some("auth_error") => bail!("server rejected the pairing token: {}", reply["message"]),
+            Some("protocol_error") => bail!("{}", reply["message"].as_str().unwrap_or("incompatible server")),
+            _ => bail!("unexpected reply from server: {}", reply),
+        }
+
+        client
+            .request(
+                "initialize",
+                serde_json::json!({
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": {},
+                    "clientInfo": { "name": "browsehand-cli", "version": env!("CARGO_PKG_VERSION") }
+                }),
+            )
+            .await?;
+        client
+            .send(serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+            .await?;
+        Ok(client)
+    }
+
+    async fn send(&mut self, message: Value) -> Result<()> {
+        self.ws.send(Message::Text(message.to_string())).await?;
+        Ok(())
+    }
+
+    async fn next_message(&mut self) -> Result<Value> {
+        loop {
+            match self.ws.next().await {
+                Some(Ok(Message::Text(text))) => return Ok(serde_json::from_str(&text)?),
+                Some(Ok(Message::Close(_))) | None => bail!("server closed the connection"),
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(e.into()),
+            }
+        }
+    }
+
+    async fn request(&mut self, method: &str, params: Value) -> Result<Value> {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(serde_json::json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+            .await?;
+        loop {
+            let message = self.next_message().await?;
+            if message["id"] != id {
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                bail!("{}", error["message"].as_str().unwrap_or("request failed"));
+            }
+            return Ok(message["result"].clone());
+        }
+    }
+
+    async fn call_tool(&mut self, name: &str, arguments: Value) -> Result<Value> {
+        self.request("tools/call", serde_json::json!({ "name": name, "arguments": arguments }))
+            .await
+    }
+}
+
+pub async fn status(config: &Config) -> Result<ExitCode> {
+    let mut client = match ControlClient::connect(config).await {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            return Ok(ExitCode::from(2));
+        }
+    };
+    let result = client.call_tool("list_browsers", serde_json::json!({})).await?;
+    let browsers = result["structuredContent"]["browsers"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+
+    println!("Server: {}", config.client_url());
+    if browsers.is_empty() {
+        println!("No browsers connected.");
+        return Ok(ExitCode::from(1));
+    }
+    println!("Browsers connected ({}):", browsers.len());
+    for browser in &browsers {
+        println!(
+            "  {}  {}, extension {}, connected {}s",
+            browser["name"].as_str().unwrap_or("?"),
+            browser["address"].as_str().unwrap_or("?"),
+            browser["extensionVersion"].as_str().unwrap_or("unknown"),
+            browser["connectedSecs"]
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+pub async fn call(config: &Config, tool: &str, args: &str, json: bool) -> Result<ExitCode> {
+    let arguments: Value = serde_json::from_str(args).context("--args must be a JSON object")?;
+    if !arguments.is_object() {
+        bail!("--args must be a JSON object");
+    }
+
+    let mut client = ControlClient::connect(config).await?;
+    let result = client.call_tool(tool, arguments).await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        for content in result["content"].as_array().into_iter().flatten() {
+            match content["type"].as_str() {
+                Some("text") => println!("{}", content["text"].as_str().unwrap_or_default()),
+                Some("image") => println!(
+                    "[image {}, {} bytes base64]",
+                    content["mimeType"].as_str().unwrap_or("?"),
+                    content["data"].as_str().map(str::len).unwrap_or(0)
+                ),
+                _ => println!("{}", content),
+            }
+        }
+    }
+
+    if result["isError"].as_bool().unwrap_or(false) {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Writes the settings from flags/env (or the defaults) to the config file and
+/// makes sure a pairing token exists.
+pub fn config_init(args: &ConfigArgs, force: bool) -> Result<ExitCode> {
+    let path = args.config.clone().unwrap_or_else(config::config_path);
+    if path.exists() && !force {
+        bail!("{} already exists (use --force to overwrite)", path.display());
+    }
+
+    let config = Config::default().with_overrides(args)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, toml::to_string_pretty(&config)?)
+        .with_context(|| format!("cannot write {}", path.display()))?;
+    Pairing::load_or_create(config.extension_ids.clone())?;
+
+    println!("Wrote {}", path.display());
+    println!("Pairing token: {}", auth::token_path().display());
+    Ok(ExitCode::SUCCESS)
+}
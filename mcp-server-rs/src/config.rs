@@ -0,0 +1,317 @@
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::auth;
+
+/// Directory holding the config file, jobs, the pairing token and TLS files.
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("browsehand")
+}
+
+pub fn config_path() -> PathBuf {
+    config_dir().join("config.toml")
+}
+
+const DEFAULT_FILE_EXTENSIONS: &[&str] = &[
+    "csv", "tsv", "json", "jsonl", "ndjson", "xlsx", "parquet", "sqlite", "sqlite3", "db", "md", "markdown",
+    "txt", "pdf", "doc", "docx", "png", "jpg", "jpeg", "gif", "webp", "zip",
+];
+
+/// Server settings. Defaults are overridden by the TOML file, which is in turn
+/// overridden by environment variables and command-line flags.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Address the extension bridge listens on.
+    pub bind: IpAddr,
+    pub port: u16,
+    /// Serve the bridge as wss://. Without `cert`/`key` a self-signed
+    /// certificate for localhost is generated in the config directory.
+    pub tls: bool,
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+    /// Exit with an error when the bridge cannot listen instead of serving MCP
+    /// without a browser. Off by default, so a second client started while
+    /// the port is taken still serves MCP.
+    pub fail_fast: bool,
+    /// Extension ids allowed to connect (checked against the `Origin` header).
+    pub extension_ids: Vec<String>,
+    /// Serve MCP over streamable HTTP on `http_port` (same bind address)
+    /// instead of stdio, so several clients can share one server.
+    pub http: bool,
+    pub http_port: u16,
+    /// Seconds to wait for the extension to answer a request, on top of any
+    /// waiting the request itself asks for (e.g. `wait_for_element`'s timeout).
+    pub request_timeout_secs: u64,
+    /// Seconds a request waits for a browser to (re)connect before failing
+    /// with "not connected"; 0 fails at once and never retries.
+    pub reconnect_grace_secs: u64,
+    /// Seconds between protocol `ping` requests to each extension, answered by
+    /// its service worker; a connection that answers nothing for three
+    /// intervals is closed. 0 turns them off.
+    pub heartbeat_secs: u64,
+    /// Requests one browser may be working on at once, across its tabs.
+    /// Requests for the same tab always run one after another.
+    pub max_concurrency: usize,
+    /// Requests that may wait for one tab before new ones are refused.
+    pub max_queue_depth: usize,
+    /// Browser events (navigation, console errors, dialogs, ...) kept for
+    /// `get_events`; the oldest are dropped first.
+    pub event_buffer: usize,
+    /// Folder the file tools save to and upload from; file names resolve
+    /// inside it. Default: `BrowseHand` on the Desktop (or in the home
+    /// directory when there is no Desktop).
+    pub output_dir: Option<PathBuf>,
+    /// Let file tools take absolute paths, reaching anywhere the user can
+    /// write. Off by default so a page cannot talk the agent into writing
+    /// e.g. `~/.ssh/authorized_keys`.
+    pub allow_absolute_paths: bool,
+    /// File extensions the file tools may read or write.
+    pub file_extensions: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8765,
+            tls: false,
+            cert: None,
+            key: None,
+            fail_fast: false,
+            extension_ids: vec![auth::EXTENSION_ID.to_string()],
+            http: false,
+            http_port: 8766,
+            request_timeout_secs: 30,
+            reconnect_grace_secs: 10,
+            heartbeat_secs: 15,
+            max_concurrency: 4,
+            max_queue_depth: 32,
+            event_buffer: 1000,
+            output_dir: None,
+            allow_absolute_paths: false,
+            file_extensions: DEFAULT_FILE_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+}
+
+/// Command-line flags (each also readable from an environment variable) that
+/// override the config file.
+#[derive(Debug, Default, Args)]
+pub struct ConfigArgs {
+    /// Config file (default: browsehand/config.toml in the user config directory)
+    #[arg(long, env = "BROWSEHAND_CONFIG", global = true)]
+    pub config: Option<PathBuf>,
+    /// Address the extension bridge listens on
+    #[arg(long, env = "BROWSEHAND_BIND", global = true)]
+    pub bind: Option<IpAddr>,
+    /// Port the extension bridge listens on
+    #[arg(long, env = "BROWSEHAND_PORT", global = true)]
+    pub port: Option<u16>,
+    /// Serve the bridge over wss://
+    #[arg(long, env = "BROWSEHAND_TLS", num_args = 0..=1, default_missing_value = "true", global = true)]
+    pub tls: Option<bool>,
+    /// PEM certificate for wss://
+    #[arg(long, env = "BROWSEHAND_TLS_CERT", global = true)]
+    pub cert: Option<PathBuf>,
+    /// PEM private key for wss://
+    #[arg(long, env = "BROWSEHAND_TLS_KEY", global = true)]
+    pub key: Option<PathBuf>,
+    /// Exit if the bridge cannot listen (default: false)
+    #[arg(long, env = "BROWSEHAND_FAIL_FAST", num_args = 0..=1, default_missing_value = "true", global = true)]
+    pub fail_fast: Option<bool>,
+    /// Extension id allowed to connect; repeat or comma-separate for several
+    #[arg(long = "extension-id", env = "BROWSEHAND_EXTENSION_ID", value_delimiter = ',', global = true)]
+    pub extension_ids: Vec<String>,
+    /// Serve MCP over HTTP instead of stdio
+    #[arg(long, env = "BROWSEHAND_HTTP", num_args = 0..=1, default_missing_value = "true", global = true)]
+    pub http: Option<bool>,
+    /// Port for MCP over HTTP
+    #[arg(long, env = "BROWSEHAND_HTTP_PORT", global = true)]
+    pub http_port: Option<u16>,
+    /// Seconds to wait for the extension to answer a request
+    #[arg(long = "request-timeout", env = "BROWSEHAND_REQUEST_TIMEOUT", global = true)]
+    pub request_timeout_secs: Option<u64>,
+    /// Seconds to wait for a browser to reconnect (0 to fail at once)
+    #[arg(long = "reconnect-grace", env = "BROWSEHAND_RECONNECT_GRACE", global = true)]
+    pub reconnect_grace_secs: Option<u64>,
+    /// Seconds between `ping` requests to the extension (0 to disable)
+    #[arg(long = "heartbeat", env = "BROWSEHAND_HEARTBEAT", global = true)]
+    pub heartbeat_secs: Option<u64>,
+    /// Requests one browser may be working on at once
+    #[arg(long, env = "BROWSEHAND_MAX_CONCURRENCY", global = true)]
+    pub max_concurrency: Option<usize>,
+    /// Requests that may wait for one tab
+    #[arg(long = "max-queue", env = "BROWSEHAND_MAX_QUEUE", global = true)]
+    pub max_queue_depth: Option<usize>,
+    /// Browser events kept for get_events
+    #[arg(long, env = "BROWSEHAND_EVENT_BUFFER", global = true)]
+    pub event_buffer: Option<usize>,
+    /// Folder file tools save to and upload from
+    #[arg(long, env = "BROWSEHAND_OUTPUT_DIR", global = true)]
+    pub output_dir: Option<PathBuf>,
+    /// Let file tools use absolute paths outside the output folder
+    #[arg(long, env = "BROWSEHAND_ALLOW_ABSOLUTE_PATHS", num_args = 0..=1, default_missing_value = "true", global = true)]
+    pub allow_absolute_paths: Option<bool>,
+    /// File extension file tools may use; repeat or comma-separate for several
+    #[arg(long = "file-extension", env = "BROWSEHAND_FILE_EXTENSION", value_delimiter = ',', global = true)]
+    pub file_extensions: Vec<String>,
+}
+
+impl Config {
+    /// Reads the config file (a missing default file is fine, a missing
+    /// explicit one is not) and applies flag/env overrides.
+    pub fn load(args: &ConfigArgs) -> Result<Self> {
+        let path = args.config.clone().unwrap_or_else(config_path);
+        let config = match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content)
+                .with_context(|| format!("invalid config file {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && args.config.is_none() => Self::default(),
+            Err(e) => return Err(e).with_context(|| format!("cannot read config file {}", path.display())),
+        };
+        config.with_overrides(args)
+    }
+
+    pub fn with_overrides(mut self, args: &ConfigArgs) -> Result<Self> {
+        if let Some(bind) = args.bind {
+            self.bind = bind;
+        }
+        if let Some(port) = args.port {
+            self.port = port;
+        }
+        if let Some(tls) = args.tls {
+            self.tls = tls;
+        }
+        if args.cert.is_some() {
+            self.cert = args.cert.clone();
+        }
+        if args.key.is_some() {
+            self.key = args.key.clone();
+        }
+        if let Some(fail_fast) = args.fail_fast {
+            self.fail_fast = fail_fast;
+        }
+        if !args.extension_ids.is_empty() {
+            self.extension_ids = args.extension_ids.clone();
+        }
+        if let Some(http) = args.http {
+            self.http = http;
+        }
+        if let Some(http_port) = args.http_port {
+            self.http_port = http_port;
+        }
+        if let Some(secs) = args.request_timeout_secs {
+            self.request_timeout_secs = secs;
+        }
+        if let Some(secs) = args.reconnect_grace_secs {
+            self.reconnect_grace_secs = secs;
+        }
+        if let Some(secs) = args.heartbeat_secs {
+            self.heartbeat_secs = secs;
+        }
+        if let Some(max) = args.max_concurrency {
+            self.max_concurrency = max;
+        }
+        if let Some(max) = args.max_queue_depth {
+            self.max_queue_depth = max;
+        }
+        if let Some(size) = args.event_buffer {
+            self.event_buffer = size;
+        }
+        if args.output_dir.is_some() {
+            self.output_dir = args.output_dir.clone();
+        }
+        if let Some(allow) = args.allow_absolute_paths {
+            self.allow_absolute_paths = allow;
+        }
+        if !args.file_extensions.is_empty() {
+            self.file_extensions = args.file_extensions.clone();
+        }
+
+        if self.cert.is_some() != self.key.is_some() {
+            bail!("TLS needs both `cert` and `key`");
+        }
+        if self.extension_ids.is_empty() {
+            bail!("`extension_ids` must list at least one extension id");
+        }
+        if self.request_timeout_secs == 0 {
+            bail!("`request_timeout_secs` must be at least 1");
+        }
+        if self.max_concurrency == 0 || self.max_queue_depth == 0 {
+            bail!("`max_concurrency` and `max_queue_depth` must be at least 1");
+        }
+        if self.event_buffer == 0 {
+            bail!("`event_buffer` must be at least 1");
+        }
+        if self.file_extensions.is_empty() {
+            bail!("`file_extensions` must list at least one extension");
+        }
+        Ok(self)
+    }
+
+    pub fn bridge_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind, self.port)
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+
+    pub fn reconnect_grace(&self) -> Duration {
+        Duration::from_secs(self.reconnect_grace_secs)
+    }
+
+    pub fn heartbeat(&self) -> Duration {
+        Duration::from_secs(self.heartbeat_secs)
+    }
+
+    pub fn output_dir(&self) -> PathBuf {
+        self.output_dir.clone().unwrap_or_else(|| {
+            dirs::desktop_dir()
+                .or_else(dirs::home_dir)
+                .unwrap_or_else(|| PathBuf::from("."))
+                .join("BrowseHand")
+        })
+    }
+
+    pub fn http_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind, self.http_port)
+    }
+
+    /// Where a local client reaches the bridge: the bind address, or loopback
+    /// when listening on all interfaces.
+    pub fn client_addr(&self) -> SocketAddr {
+        let ip = match self.bind {
+            IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            ip => ip,
+        };
+        SocketAddr::new(ip, self.port)
+    }
+
+    pub fn client_url(&self) -> String {
+        format!("{}://{}", if self.tls { "wss" } else { "ws" }, self.client_addr())
+    }
+}
+
+/// Writes a file readable only by the current user (tokens, private keys).
+pub fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(content.as_bytes())
+}
@@ -0,0 +1,114 @@
+use rmcp::model::{CallToolResult, Content};
+use thiserror::Error;
+
+use crate::protocol::Failure;
+
+pub type Result<T> = std::result::Result<T, BrowseHandError>;
+
+/// Failures surfaced to MCP clients. Each variant carries a stable,
+/// machine-readable code in the tool result's structured content so clients
+/// can tell e.g. a timeout from a missing element without parsing text.
+#[derive(Debug, Error)]
+pub enum BrowseHandError {
+    #[error("Extension not connected")]
+    NotConnected,
+    #[error("Browser session not connected: {0}")]
+    BrowserNotFound(String),
+    #[error("Multiple browsers connected ({0}); specify `browser`")]
+    AmbiguousBrowser(String),
+    #[error("Connection to extension lost")]
+    Disconnected,
+    #[error("Request timeout")]
+    Timeout,
+    #[error("Request cancelled")]
+    Cancelled,
+    #[error("Too many requests queued for {0}; try again later")]
+    QueueFull(String),
+    #[error("Extension protocol error: {0}")]
+    Protocol(String),
+    #[error("Tab not found: {0}")]
+    TabNotFound(String),
+    #[error("Element not found: {0}")]
+    SelectorNotFound(String),
+    #[error("Navigation failed: {0}")]
+    NavigationFailed(String),
+    #[error("Script error: {0}")]
+    ScriptException(String),
+    #[error("{0}")]
+    Extension(String),
+    #[error("Challenge was not solved in time: {0}")]
+    ChallengeUnresolved(String),
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("Path not allowed: {0}")]
+    PathNotAllowed(String),
+    #[error("Export failed: {0}")]
+    Export(String),
+    #[error("File I/O error: {0}")]
+    FileIo(#[from] std::io::Error),
+}
+
+impl BrowseHandError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotConnected => "not_connected",
+            Self::BrowserNotFound(_) => "browser_not_found",
+            Self::AmbiguousBrowser(_) => "ambiguous_browser",
+            Self::Disconnected => "disconnected",
+            Self::Timeout => "timeout",
+            Self::Cancelled => "cancelled",
+            Self::QueueFull(_) => "queue_full",
+            Self::Protocol(_) => "protocol_error",
+            Self::TabNotFound(_) => "tab_not_found",
+            Self::SelectorNotFound(_) => "selector_not_found",
+            Self::NavigationFailed(_) => "navigation_failed",
+            Self::ScriptException(_) => "script_exception",
+            Self::Extension(_) => "extension_error",
+            Self::ChallengeUnresolved(_) => "challenge_unresolved",
+            Self::InvalidArgument(_) => "invalid_argument",
+            Self::PathNotAllowed(_) => "path_not_allowed",
+            Self::Export(_) => "export_failed",
+            Self::FileIo(_) => "file_io",
+        }
+    }
+
+    /// Failures that may go away on their own when the request is repeated:
+    /// a slow or reconnecting extension, a full queue, or a tab whose content
+    /// script is not loaded yet.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Timeout | Self::Disconnected | Self::QueueFull(_) | Self::Extension(_))
+    }
+
+    /// Maps an extension reply with `success: false` to an error, using the
+    /// `code` the extension attached and `fallback` when there is none.
+    pub fn from_failure(failure: Failure, fallback: impl FnOnce(String) -> Self) -> Self {
+        let message = failure.error.unwrap_or_else(|| "unknown".to_string());
+        match failure.code.as_deref() {
+            Some("tab_not_found") => Self::TabNotFound(failure.tab_id.map_or(message, |id| id.to_string())),
+            Some("selector_not_found") => Self::SelectorNotFound(failure.selector.unwrap_or(message)),
+            Some("navigation_failed") => Self::NavigationFailed(message),
+            Some("script_exception") => Self::ScriptException(message),
+            Some("cancelled") => Self::Cancelled,
+            _ => fallback(message),
+        }
+    }
+}
+
+impl From<csv::Error> for BrowseHandError {
+    fn from(err: csv::Error) -> Self {
+        Self::FileIo(err.into())
+    }
+}
+
+impl From<BrowseHandError> for CallToolResult {
+    fn from(err: BrowseHandError) -> Self {
+        let mut result = CallToolResult::error(vec![Content::text(format!("Error: {}", err))]);
+        result.structured_content = Some(serde_json::json!({
+            "error": {
+                "code": err.code(),
+                "message": err.to_string(),
+            }
+        }));
+        result
+    }
+}
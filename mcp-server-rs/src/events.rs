@@ -0,0 +1,122 @@
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::protocol::{BrowserEvent, EventKind};
+
+/// An event as kept in the log. `seq` increases by one per event across all
+/// browsers and doubles as the cursor for `get_events`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedEvent {
+    pub seq: u64,
+    pub browser: String,
+    pub at: DateTime<Local>,
+    #[serde(flatten)]
+    pub event: BrowserEvent,
+}
+
+/// Which events a query wants.
+#[derive(Debug, Default)]
+pub struct EventFilter<'a> {
+    /// Only events after this cursor, oldest first. Without it the newest
+    /// events are returned.
+    pub since: Option<u64>,
+    pub browser: Option<&'a str>,
+    pub tab_id: Option<i64>,
+    /// Empty means every kind.
+    pub kinds: &'a [EventKind],
+    pub limit: usize,
+}
+
+/// Result of a query.
+pub struct EventPage {
+    pub events: Vec<RecordedEvent>,
+    /// Pass as `since` to get only what comes after this page.
+    pub cursor: u64,
+    /// Events after `since` that were dropped from the log before being read.
+    pub missed: u64,
+}
+
+impl EventPage {
+    pub fn to_json(&self) -> Value {
+        serde_json::json!({
+            "events": self.events,
+            "cursor": self.cursor,
+            "missed": self.missed,
+        })
+    }
+}
+
+struct Log {
+    events: VecDeque<RecordedEvent>,
+    last_seq: u64,
+}
+
+/// The most recent events of all browsers, in a ring buffer of `capacity`;
+/// the oldest are dropped first.
+pub struct EventLog {
+    log: Mutex<Log>,
+    capacity: usize,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            log: Mutex::new(Log {
+                events: VecDeque::with_capacity(capacity),
+                last_seq: 0,
+            }),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn push(&self, browser: &str, event: BrowserEvent) {
+        let mut log = self.log.lock().expect("event log lock");
+        if log.events.len() == self.capacity {
+            log.events.pop_front();
+        }
+        log.last_seq += 1;
+        let seq = log.last_seq;
+        log.events.push_back(RecordedEvent {
+            seq,
+            browser: browser.to_string(),
+            at: Local::now(),
+            event,
+        });
+    }
+
+    pub fn query(&self, filter: &EventFilter) -> EventPage {
+        let log = self.log.lock().expect("event log lock");
+        let oldest = log.events.front().map_or(log.last_seq + 1, |e| e.seq);
+        let missed = filter.since.map_or(0, |since| oldest.saturating_sub(since + 1));
+
+        let matching = log.events.iter().filter(|e| {
+            filter.since.is_none_or(|since| e.seq > since)
+                && filter.browser.is_none_or(|b| e.browser == b)
+                && filter.tab_id.is_none_or(|tab| e.event.tab_id() == Some(tab))
+                && (filter.kinds.is_empty() || filter.kinds.contains(&e.event.kind()))
+        });
+        let events: Vec<RecordedEvent> = if filter.since.is_some() {
+            matching.take(filter.limit).cloned().collect()
+        } else {
+            let mut newest: Vec<RecordedEvent> = matching.rev().take(filter.limit).cloned().collect();
+            newest.reverse();
+            newest
+        };
+
+        // A page cut short by `limit` continues after its last event;
+        // otherwise everything up to now has been seen.
+        let cursor = match events.last() {
+            Some(last) if filter.since.is_some() && events.len() == filter.limit => last.seq,
+            _ => log.last_seq,
+        };
+        EventPage { events, cursor, missed }
+    }
+}
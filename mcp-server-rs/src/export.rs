@@ -0,0 +1,1001 @@
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use rmcp::schemars;
+use rusqlite::types::Value as SqlValue;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::error::{BrowseHandError, Result};
+use crate::flatten::{self, Flatten, Row};
+
+/// File formats `save_data` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    Csv,
+    Tsv,
+    Json,
+    Jsonl,
+    Xlsx,
+    Parquet,
+    Sqlite,
+    Markdown,
+}
+
+impl Format {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Tsv => "tsv",
+            Self::Json => "json",
+            Self::Jsonl => "jsonl",
+            Self::Xlsx => "xlsx",
+            Self::Parquet => "parquet",
+            Self::Sqlite => "sqlite",
+            Self::Markdown => "markdown",
+        }
+    }
+
+    /// The format a file name's extension stands for.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        Some(match ext.as_str() {
+            "csv" => Self::Csv,
+            "tsv" => Self::Tsv,
+            "json" => Self::Json,
+            "jsonl" | "ndjson" => Self::Jsonl,
+            "xlsx" => Self::Xlsx,
+            "parquet" => Self::Parquet,
+            "sqlite" | "sqlite3" | "db" => Self::Sqlite,
+            "md" | "markdown" => Self::Markdown,
+            _ => return None,
+        })
+    }
+
+    /// The writer for a tabular format; `None` for JSON, which takes data of
+    /// any shape (see [`save_json`]).
+    fn writer(self, options: &SaveOptions) -> Option<Box<dyn TableWriter>> {
+        Some(match self {
+            Self::Csv => Box::new(Delimited { delimiter: b',', quote: csv::QuoteStyle::Always }),
+            Self::Tsv => Box::new(Delimited { delimiter: b'\t', quote: csv::QuoteStyle::Necessary }),
+            Self::Json => return None,
+            Self::Jsonl => Box::new(JsonLines),
+            Self::Xlsx => Box::new(Xlsx),
+            Self::Parquet => Box::new(Parquet),
+            Self::Sqlite => Box::new(Sqlite {
+                table: options.table.clone().unwrap_or_else(|| DEFAULT_TABLE.to_string()),
+            }),
+            Self::Markdown => Box::new(Markdown),
+        })
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+const DEFAULT_TABLE: &str = "data";
+
+/// How a JSON save combines its data with the file already there.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonMode {
+    /// Replace the file.
+    #[default]
+    Overwrite,
+    /// Add the items (or the value) to the end of the file's array.
+    Append,
+    /// Merge an object into the file's object, recursing into nested objects.
+    Merge,
+    /// Replace the items of the file's array whose `key` matches, add the rest.
+    Upsert,
+}
+
+/// How rows are turned into a table and written.
+#[derive(Debug, Default)]
+pub struct SaveOptions {
+    /// Add to an existing file instead of replacing it (see each writer).
+    pub append: bool,
+    /// Columns to write, in order; every other key is left out.
+    pub columns: Option<Vec<String>>,
+    pub flatten: Option<Flatten>,
+    /// SQLite table name.
+    pub table: Option<String>,
+    /// JSON only; `append` alone means [`JsonMode::Append`].
+    pub json_mode: JsonMode,
+    /// Field identifying an item for [`JsonMode::Upsert`].
+    pub key: Option<String>,
+}
+
+/// Rows with the columns to write them under.
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Row>,
+}
+
+impl Table {
+    /// Builds a table from `data`, a non-empty array of objects. Without pinned
+    /// `columns` the columns are every key, by the row it first appears in;
+    /// keys new in the same row come alphabetically, as JSON objects keep them.
+    pub fn new(data: &Value, options: &SaveOptions) -> Result<Self> {
+        let arr = data
+            .as_array()
+            .ok_or_else(|| BrowseHandError::InvalidArgument("data must be an array".into()))?;
+        if arr.is_empty() {
+            return Err(BrowseHandError::InvalidArgument("data array is empty".into()));
+        }
+        let mut rows = flatten::rows(arr, options.flatten.as_ref())?;
+
+        let Some(columns) = &options.columns else {
+            let mut seen = HashSet::new();
+            let columns = rows
+                .iter()
+                .flat_map(Row::columns)
+                .filter(|c| seen.insert(c.as_str()))
+                .cloned()
+                .collect();
+            return Ok(Self { columns, rows });
+        };
+        if columns.is_empty() {
+            return Err(BrowseHandError::InvalidArgument("columns must not be empty".into()));
+        }
+        let mut seen = HashSet::new();
+        if let Some(dup) = columns.iter().find(|c| !seen.insert(c.as_str())) {
+            return Err(BrowseHandError::InvalidArgument(format!("duplicate column '{}'", dup)));
+        }
+        for row in &mut rows {
+            row.retain(|c| seen.contains(c));
+        }
+        Ok(Self { columns: columns.clone(), rows })
+    }
+
+    /// Cell texts of `row` under `columns`, blank where it has no value.
+    fn texts<'a>(row: &'a Row, columns: &'a [String]) -> impl Iterator<Item = String> + 'a {
+        columns.iter().map(|c| row.get(c).map(flatten::cell).unwrap_or_default())
+    }
+
+    fn object(&self, row: &Row) -> Map<String, Value> {
+        self.columns
+            .iter()
+            .filter_map(|c| row.get(c).map(|v| (c.clone(), v.clone())))
+            .collect()
+    }
+
+    /// `existing` followed by the columns of this table it lacks.
+    fn widen(&self, existing: &[String]) -> Vec<String> {
+        let mut columns = existing.to_vec();
+        columns.extend(self.columns.iter().filter(|c| !existing.contains(c)).cloned());
+        columns
+    }
+}
+
+/// Writes a table in one file format.
+trait TableWriter {
+    /// Creates or replaces the file.
+    fn write(&self, path: &Path, table: &Table) -> Result<()>;
+
+    /// Adds the rows to an existing, non-empty file.
+    fn append(&self, _path: &Path, _table: &Table) -> Result<()> {
+        Err(BrowseHandError::InvalidArgument(
+            "this format cannot be appended to; use csv, tsv, json, jsonl, markdown or sqlite".into(),
+        ))
+    }
+}
+
+/// Saves `data` to `path` as `format`.
+pub fn save(path: &Path, format: Format, data: &Value, options: &SaveOptions) -> Result<()> {
+    let Some(writer) = format.writer(options) else {
+        return save_json(path, data, options);
+    };
+    let table = Table::new(data, options)?;
+    if options.append && exists(path)? {
+        writer.append(path, &table)
+    } else {
+        writer.write(path, &table)
+    }
+}
+
+fn exists(path: &Path) -> Result<bool> {
+    Ok(path.exists() && fs::metadata(path)?.len() > 0)
+}
+
+/// Writes `content` to a temporary file next to `path` and renames it over
+/// `path`, so a crash mid-write leaves the old file whole.
+pub(crate) fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let tmp = dir.join(format!(".{}.{}.tmp", name, Uuid::new_v4().simple()));
+    let written = File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(content)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    Ok(written?)
+}
+
+/// Saves JSON, combining it with the existing file as `options.json_mode`
+/// says. Without `columns` or `flatten` the data is stored as it is, whatever
+/// its shape; with them it goes through a [`Table`] first.
+fn save_json(path: &Path, data: &Value, options: &SaveOptions) -> Result<()> {
+    let data = if options.columns.is_some() || options.flatten.is_some() {
+        let table = Table::new(data, options)?;
+        Value::Array(table.rows.iter().map(|r| Value::Object(table.object(r))).collect())
+    } else {
+        data.clone()
+    };
+    let mode = match options.json_mode {
+        JsonMode::Overwrite if options.append => JsonMode::Append,
+        mode => mode,
+    };
+
+    let existing = if mode != JsonMode::Overwrite && exists(path)? {
+        let content = fs::read_to_string(path)?;
+        Some(serde_json::from_str::<Value>(&content).map_err(|e| {
+            BrowseHandError::InvalidArgument(format!("existing file is not valid JSON ({}); it was left as is", e))
+        })?)
+    } else {
+        None
+    };
+    let combined = match mode {
+        JsonMode::Overwrite => data,
+        JsonMode::Append => {
+            let mut items = existing_array(existing, "append")?;
+            match data {
+                Value::Array(new) => items.extend(new),
+                value => items.push(value),
+            }
+            Value::Array(items)
+        }
+        JsonMode::Merge => {
+            let mut target = match existing {
+                None => Value::Object(Map::new()),
+                Some(value @ Value::Object(_)) => value,
+                Some(_) => {
+                    return Err(BrowseHandError::InvalidArgument(
+                        "existing file is not a JSON object; cannot merge".into(),
+                    ))
+                }
+            };
+            if !data.is_object() {
+                return Err(BrowseHandError::InvalidArgument("merge needs an object as data".into()));
+            }
+            merge(&mut target, data);
+            target
+        }
+        JsonMode::Upsert => {
+            let key = options
+                .key
+                .as_deref()
+                .ok_or_else(|| BrowseHandError::InvalidArgument("upsert needs `key`".into()))?;
+            let mut items = existing_array(existing, "upsert")?;
+            let new = match data {
+                Value::Array(new) => new,
+                value => vec![value],
+            };
+            for item in new {
+                let id = item.get(key).filter(|id| !id.is_null()).cloned().ok_or_else(|| {
+                    BrowseHandError::InvalidArgument(format!("every item needs the key field '{}'", key))
+                })?;
+                match items.iter_mut().find(|old| old.get(key) == Some(&id)) {
+                    Some(old) => *old = item,
+                    None => items.push(item),
+                }
+            }
+            Value::Array(items)
+        }
+    };
+
+    let content = serde_json::to_string_pretty(&combined).map_err(|e| BrowseHandError::InvalidArgument(e.to_string()))?;
+    write_atomic(path, content.as_bytes())
+}
+
+fn existing_array(existing: Option<Value>, mode: &str) -> Result<Vec<Value>> {
+    match existing {
+        None => Ok(Vec::new()),
+        Some(Value::Array(items)) => Ok(items),
+        Some(_) => Err(BrowseHandError::InvalidArgument(format!(
+            "existing file is not a JSON array; cannot {}",
+            mode
+        ))),
+    }
+}
+
+/// Merges `patch` into `target`: objects key by key, recursively; anything
+/// else replaces what was there.
+fn merge(target: &mut Value, patch: Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(&key) {
+                    Some(old) => merge(old, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch,
+    }
+}
+
+fn export_error(err: impl fmt::Display) -> BrowseHandError {
+    BrowseHandError::Export(err.to_string())
+}
+
+/// CSV and TSV, written in memory and renamed over the old file. Appending
+/// maps rows onto the file's header by column name; columns it lacks are
+/// added by rewriting the file with the widened header.
+struct Delimited {
+    delimiter: u8,
+    quote: csv::QuoteStyle,
+}
+
+impl Delimited {
+    fn writer<W: Write>(&self, out: W) -> csv::Writer<W> {
+        csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .quote_style(self.quote)
+            .terminator(csv::Terminator::Any(b'\n'))
+            .from_writer(out)
+    }
+
+    fn read(&self, path: &Path) -> Result<(Vec<String>, Vec<csv::StringRecord>)> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .flexible(true)
+            .from_path(path)?;
+        let header = reader.headers()?.iter().map(str::to_string).collect();
+        let records = reader.records().collect::<std::result::Result<_, _>>()?;
+        Ok((header, records))
+    }
+
+    fn write_rows<W: Write>(&self, writer: &mut csv::Writer<W>, columns: &[String], table: &Table) -> Result<()> {
+        for row in &table.rows {
+            writer.write_record(Table::texts(row, columns))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl TableWriter for Delimited {
+    fn write(&self, path: &Path, table: &Table) -> Result<()> {
+        let mut writer = self.writer(Vec::new());
+        writer.write_record(&table.columns)?;
+        self.write_rows(&mut writer, &table.columns, table)?;
+        let content = writer.into_inner().map_err(|e| export_error(e.error()))?;
+        write_atomic(path, &content)
+    }
+
+    fn append(&self, path: &Path, table: &Table) -> Result<()> {
+        let (header, records) = self.read(path)?;
+        let columns = table.widen(&header);
+        if columns.len() == header.len() {
+            // Start on a new line if the last one was left open.
+            let unterminated = fs::read(path)?.last().is_some_and(|b| *b != b'\n');
+            let mut file = OpenOptions::new().append(true).open(path)?;
+            if unterminated {
+                file.write_all(b"\n")?;
+            }
+            return self.write_rows(&mut self.writer(file), &columns, table);
+        }
+        let mut writer = self.writer(Vec::new());
+        writer.write_record(&columns)?;
+        for record in &records {
+            writer.write_record((0..columns.len()).map(|i| record.get(i).unwrap_or("")))?;
+        }
+        self.write_rows(&mut writer, &columns, table)?;
+        let content = writer.into_inner().map_err(|e| export_error(e.error()))?;
+        write_atomic(path, &content)
+    }
+}
+
+/// One JSON object per line; appending adds lines.
+struct JsonLines;
+
+impl JsonLines {
+    fn write_rows(file: File, table: &Table) -> Result<()> {
+        let mut out = BufWriter::new(file);
+        for row in &table.rows {
+            serde_json::to_writer(&mut out, &table.object(row)).map_err(export_error)?;
+            out.write_all(b"\n")?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+impl TableWriter for JsonLines {
+    fn write(&self, path: &Path, table: &Table) -> Result<()> {
+        Self::write_rows(File::create(path)?, table)
+    }
+
+    fn append(&self, path: &Path, table: &Table) -> Result<()> {
+        // Start on a new line if the last one was left open.
+        let unterminated = fs::read(path)?.last().is_some_and(|b| *b != b'\n');
+        let mut file = OpenOptions::new().append(true).open(path)?;
+        if unterminated {
+            file.write_all(b"\n")?;
+        }
+        Self::write_rows(file, table)
+    }
+}
+
+/// A GitHub-flavoured Markdown table. Appending re-reads the table and
+/// rewrites it, widening the header like CSV.
+struct Markdown;
+
+impl Markdown {
+    fn escape(text: &str) -> String {
+        text.replace('|', "\\|").replace("\r\n", "<br>").replace('\n', "<br>")
+    }
+
+    fn line(cells: impl Iterator<Item = String>) -> String {
+        let cells: Vec<String> = cells.map(|c| Self::escape(&c)).collect();
+        format!("| {} |\n", cells.join(" | "))
+    }
+
+    fn split(line: &str) -> Vec<String> {
+        let inner = line.trim().trim_start_matches('|');
+        let inner = inner.strip_suffix('|').filter(|s| !s.ends_with('\\')).unwrap_or(inner);
+        let mut cells = vec![String::new()];
+        let mut chars = inner.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&'|') => {
+                    cells.last_mut().unwrap().push('|');
+                    chars.next();
+                }
+                '|' => cells.push(String::new()),
+                c => cells.last_mut().unwrap().push(c),
+            }
+        }
+        cells.iter().map(|c| c.trim().to_string()).collect()
+    }
+
+    fn render(columns: &[String], old: &[Vec<String>], table: &Table) -> String {
+        let mut out = Self::line(columns.iter().cloned());
+        out.push_str(&Self::line(columns.iter().map(|_| "---".to_string())));
+        for cells in old {
+            out.push_str(&Self::line((0..columns.len()).map(|i| cells.get(i).cloned().unwrap_or_default())));
+        }
+        for row in &table.rows {
+            out.push_str(&Self::line(Table::texts(row, columns)));
+        }
+        out
+    }
+}
+
+impl TableWriter for Markdown {
+    fn write(&self, path: &Path, table: &Table) -> Result<()> {
+        write_atomic(path, Self::render(&table.columns, &[], table).as_bytes())
+    }
+
+    fn append(&self, path: &Path, table: &Table) -> Result<()> {
+        let content = fs::read_to_string(path)?;
+        let mut lines = content.lines().filter(|l| l.trim_start().starts_with('|'));
+        let header = lines.next().map(Self::split).unwrap_or_default();
+        // Skip the `| --- |` separator.
+        let old: Vec<Vec<String>> = lines.skip(1).map(Self::split).collect();
+        write_atomic(path, Self::render(&table.widen(&header), &old, table).as_bytes())
+    }
+}
+
+/// A column's type, for formats that store types: the narrowest one that
+/// holds every non-null value.
+#[derive(Clone, Copy, PartialEq)]
+enum ColumnType {
+    Boolean,
+    Integer,
+    Float,
+    Text,
+}
+
+impl ColumnType {
+    fn of(table: &Table, column: &str) -> Self {
+        let mut kind = None;
+        for value in table.rows.iter().filter_map(|r| r.get(column)) {
+            let this = match value {
+                Value::Null => continue,
+                Value::Bool(_) => Self::Boolean,
+                Value::Number(n) if n.is_i64() => Self::Integer,
+                Value::Number(_) => Self::Float,
+                _ => return Self::Text,
+            };
+            kind = Some(match (kind, this) {
+                (None, this) => this,
+                (Some(k), this) if k == this => k,
+                (Some(Self::Integer | Self::Float), Self::Integer | Self::Float) => Self::Float,
+                _ => return Self::Text,
+            });
+        }
+        kind.unwrap_or(Self::Text)
+    }
+}
+
+/// An Excel workbook with one sheet; the header row is bold and frozen.
+struct Xlsx;
+
+impl TableWriter for Xlsx {
+    fn write(&self, path: &Path, table: &Table) -> Result<()> {
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let sheet = workbook.add_worksheet();
+        let bold = rust_xlsxwriter::Format::new().set_bold();
+        for (col, name) in table.columns.iter().enumerate() {
+            sheet.write_string_with_format(0, col as u16, name, &bold).map_err(export_error)?;
+        }
+        for (i, row) in table.rows.iter().enumerate() {
+            let r = i as u32 + 1;
+            for (col, name) in table.columns.iter().enumerate() {
+                let col = col as u16;
+                match row.get(name) {
+                    None | Some(Value::Null) => continue,
+                    Some(Value::Bool(b)) => sheet.write_boolean(r, col, *b),
+                    Some(Value::Number(n)) => sheet.write_number(r, col, n.as_f64().unwrap_or_default()),
+                    Some(value) => sheet.write_string(r, col, flatten::cell(value)),
+                }
+                .map_err(export_error)?;
+            }
+        }
+        sheet.set_freeze_panes(1, 0).map_err(export_error)?;
+        sheet.autofit();
+        workbook.save(path).map_err(export_error)
+    }
+}
+
+/// An Apache Parquet file with one column per table column, typed by
+/// [`ColumnType`]; nested values are stored as JSON text.
+struct Parquet;
+
+impl TableWriter for Parquet {
+    fn write(&self, path: &Path, table: &Table) -> Result<()> {
+        let mut fields = Vec::with_capacity(table.columns.len());
+        let mut arrays: Vec<ArrayRef> = Vec::with_capacity(table.columns.len());
+        for name in &table.columns {
+            let values = table.rows.iter().map(|r| r.get(name).filter(|v| !v.is_null()));
+            let (data_type, array): (DataType, ArrayRef) = match ColumnType::of(table, name) {
+                ColumnType::Boolean => (
+                    DataType::Boolean,
+                    Arc::new(values.map(|v| v.and_then(Value::as_bool)).collect::<BooleanArray>()),
+                ),
+                ColumnType::Integer => (
+                    DataType::Int64,
+                    Arc::new(values.map(|v| v.and_then(Value::as_i64)).collect::<Int64Array>()),
+                ),
+                ColumnType::Float => (
+                    DataType::Float64,
+                    Arc::new(values.map(|v| v.and_then(Value::as_f64)).collect::<Float64Array>()),
+                ),
+                ColumnType::Text => (
+                    DataType::Utf8,
+                    Arc::new(values.map(|v| v.map(flatten::cell)).collect::<StringArray>()),
+                ),
+            };
+            fields.push(Field::new(name, data_type, true));
+            arrays.push(array);
+        }
+        let schema = Arc::new(Schema::new(fields));
+        let batch = RecordBatch::try_new(schema.clone(), arrays).map_err(export_error)?;
+        let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+        let mut writer = ArrowWriter::try_new(File::create(path)?, schema, Some(properties)).map_err(export_error)?;
+        writer.write(&batch).map_err(export_error)?;
+        writer.close().map_err(export_error)?;
+        Ok(())
+    }
+}
+
+/// A table in an SQLite database. Writing replaces only that table; appending
+/// inserts into it, adding the columns it lacks.
+struct Sqlite {
+    table: String,
+}
+
+impl Sqlite {
+    fn quote(name: &str) -> String {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+
+    fn declared_type(table: &Table, column: &str) -> &'static str {
+        match ColumnType::of(table, column) {
+            ColumnType::Boolean | ColumnType::Integer => "INTEGER",
+            ColumnType::Float => "REAL",
+            ColumnType::Text => "TEXT",
+        }
+    }
+
+    fn value(value: Option<&Value>) -> SqlValue {
+        match value {
+            None | Some(Value::Null) => SqlValue::Null,
+            Some(Value::Bool(b)) => SqlValue::Integer(*b as i64),
+            Some(Value::Number(n)) => match n.as_i64() {
+                Some(i) => SqlValue::Integer(i),
+                None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+            },
+            Some(value) => SqlValue::Text(flatten::cell(value)),
+        }
+    }
+
+    fn save(&self, path: &Path, table: &Table, replace: bool) -> rusqlite::Result<()> {
+        let mut conn = rusqlite::Connection::open(path)?;
+        let tx = conn.transaction()?;
+        let name = Self::quote(&self.table);
+        if replace {
+            tx.execute(&format!("DROP TABLE IF EXISTS {}", name), [])?;
+        }
+        let existing: Vec<String> = tx
+            .prepare("SELECT name FROM pragma_table_info(?1)")?
+            .query_map([&self.table], |r| r.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        let added: Vec<&String> = table.columns.iter().filter(|c| !existing.contains(c)).collect();
+        if existing.is_empty() {
+            let columns: Vec<String> = added
+                .iter()
+                .map(|c| format!("{} {}", Self::quote(c), Self::declared_type(table, c)))
+                .collect();
+            tx.execute(&format!("CREATE TABLE {} ({})", name, columns.join(", ")), [])?;
+        } else {
+            for column in added {
+                tx.execute(
+                    &format!(
+                        "ALTER TABLE {} ADD COLUMN {} {}",
+                        name,
+                        Self::quote(column),
+                        Self::declared_type(table, column)
+                    ),
+                    [],
+                )?;
+            }
+        }
+
+        let columns: Vec<String> = table.columns.iter().map(|c| Self::quote(c)).collect();
+        let placeholders = vec!["?"; columns.len()].join(", ");
+        {
+            let mut insert = tx.prepare(&format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                name,
+                columns.join(", "),
+                placeholders
+            ))?;
+            for row in &table.rows {
+                insert.execute(rusqlite::params_from_iter(table.columns.iter().map(|c| Self::value(row.get(c)))))?;
+            }
+        }
+        tx.commit()
+    }
+}
+
+impl TableWriter for Sqlite {
+    fn write(&self, path: &Path, table: &Table) -> Result<()> {
+        self.save(path, table, true).map_err(export_error)
+    }
+
+    fn append(&self, path: &Path, table: &Table) -> Result<()> {
+        self.save(path, table, false).map_err(export_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatten::ArrayMode;
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    /// A fresh temporary directory, removed on drop.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("browsehand-test-{}", Uuid::new_v4().simple()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.0.join(name)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn appending() -> SaveOptions {
+        SaveOptions { append: true, ..Default::default() }
+    }
+
+    fn flattened(arrays: ArrayMode) -> SaveOptions {
+        SaveOptions {
+            flatten: Some(Flatten { arrays, separator: "; ".into() }),
+            ..Default::default()
+        }
+    }
+
+    /// The table's columns followed by the cell texts of each row.
+    fn cells(data: Value, options: &SaveOptions) -> (Vec<String>, Vec<Vec<String>>) {
+        let table = Table::new(&data, options).unwrap();
+        let rows = table.rows.iter().map(|row| Table::texts(row, &table.columns).collect()).collect();
+        (table.columns, rows)
+    }
+
+    fn person() -> Value {
+        json!([{
+            "name": "Kim",
+            "address": {"city": "Seoul", "geo": {"lat": 37.5}},
+            "tags": ["a", "b"],
+            "orders": [{"id": 1, "items": ["x", "y"]}, {"id": 2, "items": []}]
+        }])
+    }
+
+    #[test]
+    fn join_puts_each_array_in_one_cell() {
+        let (columns, rows) = cells(person(), &flattened(ArrayMode::Join));
+        assert_eq!(columns, ["address.city", "address.geo.lat", "name", "orders.id", "orders.items", "tags"]);
+        assert_eq!(rows, [["Seoul", "37.5", "Kim", "1; 2", "x; y", "a; b"]]);
+    }
+
+    #[test]
+    fn explode_gives_a_row_per_item() {
+        let (columns, rows) = cells(person(), &flattened(ArrayMode::Explode));
+        assert_eq!(columns, ["address.city", "address.geo.lat", "name", "orders.id", "orders.items", "tags"]);
+        assert_eq!(
+            rows,
+            [
+                ["Seoul", "37.5", "Kim", "1", "x", "a"],
+                ["Seoul", "37.5", "Kim", "1", "x", "b"],
+                ["Seoul", "37.5", "Kim", "1", "y", "a"],
+                ["Seoul", "37.5", "Kim", "1", "y", "b"],
+                ["Seoul", "37.5", "Kim", "2", "", "a"],
+                ["Seoul", "37.5", "Kim", "2", "", "b"],
+            ]
+        );
+    }
+
+    #[test]
+    fn index_gives_a_column_per_item() {
+        let (columns, rows) = cells(person(), &flattened(ArrayMode::Index));
+        assert_eq!(
+            columns,
+            [
+                "address.city",
+                "address.geo.lat",
+                "name",
+                "orders.0.id",
+                "orders.0.items.0",
+                "orders.0.items.1",
+                "orders.1.id",
+                "orders.1.items",
+                "tags.0",
+                "tags.1",
+            ]
+        );
+        assert_eq!(rows, [["Seoul", "37.5", "Kim", "1", "x", "y", "2", "", "a", "b"]]);
+    }
+
+    #[test]
+    fn index_columns_keep_numeric_order() {
+        let tags: Vec<usize> = (0..12).collect();
+        let (columns, _) = cells(json!([{ "tags": tags }]), &flattened(ArrayMode::Index));
+        let expected: Vec<String> = (0..12).map(|i| format!("tags.{}", i)).collect();
+        assert_eq!(columns, expected);
+    }
+
+    #[test]
+    fn without_flatten_nested_values_stay_json() {
+        let (columns, rows) = cells(person(), &SaveOptions::default());
+        assert_eq!(columns, ["address", "name", "orders", "tags"]);
+        assert_eq!(rows[0][0], r#"{"city":"Seoul","geo":{"lat":37.5}}"#);
+        assert_eq!(rows[0][3], r#"["a","b"]"#);
+    }
+
+    #[test]
+    fn xlsx_refuses_append_and_keeps_the_file() {
+        let scratch = Scratch::new();
+        let path = scratch.path("out.xlsx");
+        save(&path, Format::Xlsx, &json!([{"a": 1}]), &SaveOptions::default()).unwrap();
+        let before = fs::read(&path).unwrap();
+        assert!(before.starts_with(b"PK"));
+
+        let result = save(&path, Format::Xlsx, &json!([{"a": 2}]), &appending());
+        assert!(matches!(result, Err(BrowseHandError::InvalidArgument(_))));
+        assert_eq!(fs::read(&path).unwrap(), before);
+    }
+
+    #[test]
+    fn parquet_round_trips_and_refuses_append() {
+        let scratch = Scratch::new();
+        let path = scratch.path("out.parquet");
+        let data = json!([{"n": 1, "x": 1.5, "ok": true, "s": "a"}, {"n": 2, "s": {"k": "v"}}]);
+        save(&path, Format::Parquet, &data, &SaveOptions::default()).unwrap();
+
+        let read = || {
+            let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap();
+            let reader = builder.build().unwrap();
+            reader.collect::<std::result::Result<Vec<_>, _>>().unwrap()
+        };
+        let batches = read();
+        let batch = &batches[0];
+        let schema = batch.schema();
+        let types: Vec<(&str, &DataType)> = schema.fields().iter().map(|f| (f.name().as_str(), f.data_type())).collect();
+        assert_eq!(
+            types,
+            [("n", &DataType::Int64), ("ok", &DataType::Boolean), ("s", &DataType::Utf8), ("x", &DataType::Float64)]
+        );
+        assert_eq!(batch.num_rows(), 2);
+        let s = batch.column(2).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!((s.value(0), s.value(1)), ("a", r#"{"k":"v"}"#));
+        let x = batch.column(3).as_any().downcast_ref::<Float64Array>().unwrap();
+        assert!(x.is_null(1));
+
+        let result = save(&path, Format::Parquet, &json!([{"n": 3}]), &appending());
+        assert!(matches!(result, Err(BrowseHandError::InvalidArgument(_))));
+        assert_eq!(read()[0].num_rows(), 2);
+    }
+
+    #[test]
+    fn sqlite_append_adds_missing_columns() {
+        let scratch = Scratch::new();
+        let path = scratch.path("out.sqlite");
+        save(&path, Format::Sqlite, &json!([{"id": 1, "name": "a"}]), &SaveOptions::default()).unwrap();
+        save(&path, Format::Sqlite, &json!([{"id": 2, "price": 9.5, "name": "b"}]), &appending()).unwrap();
+
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        let columns: Vec<(String, String)> = conn
+            .prepare("SELECT name, type FROM pragma_table_info('data')")
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        let expected = [("id", "INTEGER"), ("name", "TEXT"), ("price", "REAL")];
+        assert_eq!(columns, expected.map(|(n, t)| (n.to_string(), t.to_string())));
+
+        let rows: Vec<(i64, String, Option<f64>)> = conn
+            .prepare("SELECT id, name, price FROM data ORDER BY id")
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(rows, [(1, "a".to_string(), None), (2, "b".to_string(), Some(9.5))]);
+    }
+
+    #[test]
+    fn sqlite_write_replaces_only_its_table() {
+        let scratch = Scratch::new();
+        let path = scratch.path("out.sqlite");
+        let other = SaveOptions { table: Some("other".into()), ..Default::default() };
+        save(&path, Format::Sqlite, &json!([{"a": 1}]), &other).unwrap();
+        save(&path, Format::Sqlite, &json!([{"a": 1}, {"a": 2}]), &SaveOptions::default()).unwrap();
+        save(&path, Format::Sqlite, &json!([{"b": "x"}]), &SaveOptions::default()).unwrap();
+
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        let count = |table: &str| -> i64 {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |r| r.get(0)).unwrap()
+        };
+        assert_eq!((count("data"), count("other")), (1, 1));
+    }
+
+    fn json_mode(mode: JsonMode) -> SaveOptions {
+        SaveOptions { json_mode: mode, key: Some("id".into()), ..Default::default() }
+    }
+
+    /// Saves `data` over a file holding `existing`; returns the result and
+    /// the file's contents afterwards.
+    fn save_over(existing: &str, data: Value, options: &SaveOptions) -> (Result<()>, String) {
+        let scratch = Scratch::new();
+        let path = scratch.path("out.json");
+        fs::write(&path, existing).unwrap();
+        let result = save(&path, Format::Json, &data, options);
+        (result, fs::read_to_string(&path).unwrap())
+    }
+
+    fn parsed(content: &str) -> Value {
+        serde_json::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn merge_recurses_into_objects_and_replaces_the_rest() {
+        let existing = r#"{"a": {"x": 1, "y": 2}, "b": [1], "c": 1}"#;
+        let data = json!({"a": {"y": 3, "z": 4}, "b": [2], "c": {"d": 1}});
+        let (result, content) = save_over(existing, data, &json_mode(JsonMode::Merge));
+        result.unwrap();
+        assert_eq!(parsed(&content), json!({"a": {"x": 1, "y": 3, "z": 4}, "b": [2], "c": {"d": 1}}));
+    }
+
+    #[test]
+    fn merge_needs_objects_on_both_sides() {
+        let existing = r#"[{"id": 1}]"#;
+        let (result, content) = save_over(existing, json!({"a": 1}), &json_mode(JsonMode::Merge));
+        assert!(matches!(result, Err(BrowseHandError::InvalidArgument(_))));
+        assert_eq!(content, existing);
+
+        let existing = r#"{"a": 1}"#;
+        let (result, content) = save_over(existing, json!([1]), &json_mode(JsonMode::Merge));
+        assert!(matches!(result, Err(BrowseHandError::InvalidArgument(_))));
+        assert_eq!(content, existing);
+    }
+
+    #[test]
+    fn upsert_replaces_matching_items_and_adds_the_rest() {
+        let existing = r#"[{"id": 1, "v": "a"}, {"id": 2, "v": "b", "old": true}]"#;
+        let data = json!([{"id": 2, "v": "B"}, {"id": 3, "v": "c"}]);
+        let (result, content) = save_over(existing, data, &json_mode(JsonMode::Upsert));
+        result.unwrap();
+        assert_eq!(parsed(&content), json!([{"id": 1, "v": "a"}, {"id": 2, "v": "B"}, {"id": 3, "v": "c"}]));
+    }
+
+    #[test]
+    fn upsert_refuses_items_without_the_key() {
+        let existing = r#"[{"id": 1}]"#;
+        for item in [json!({"v": "x"}), json!({"id": null})] {
+            let (result, content) = save_over(existing, json!([{"id": 2}, item]), &json_mode(JsonMode::Upsert));
+            assert!(matches!(result, Err(BrowseHandError::InvalidArgument(_))));
+            assert_eq!(content, existing);
+        }
+
+        let no_key = SaveOptions { json_mode: JsonMode::Upsert, ..Default::default() };
+        let (result, _) = save_over(existing, json!([{"id": 2}]), &no_key);
+        assert!(matches!(result, Err(BrowseHandError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn append_adds_items_or_a_single_value() {
+        let (result, content) = save_over("[1]", json!([2, 3]), &appending());
+        result.unwrap();
+        assert_eq!(parsed(&content), json!([1, 2, 3]));
+
+        let (result, content) = save_over("[1]", json!({"a": 2}), &json_mode(JsonMode::Append));
+        result.unwrap();
+        assert_eq!(parsed(&content), json!([1, {"a": 2}]));
+    }
+
+    #[test]
+    fn append_and_upsert_refuse_a_non_array_file() {
+        let existing = r#"{"items": [1]}"#;
+        for mode in [JsonMode::Append, JsonMode::Upsert] {
+            let (result, content) = save_over(existing, json!([{"id": 2}]), &json_mode(mode));
+            assert!(matches!(result, Err(BrowseHandError::InvalidArgument(_))));
+            assert_eq!(content, existing);
+        }
+    }
+
+    #[test]
+    fn invalid_json_file_is_left_as_is() {
+        let (result, content) = save_over("[1,", json!([2]), &appending());
+        assert!(matches!(result, Err(BrowseHandError::InvalidArgument(_))));
+        assert_eq!(content, "[1,");
+    }
+
+    #[test]
+    fn missing_file_starts_empty() {
+        let scratch = Scratch::new();
+        let path = scratch.path("out.json");
+        save(&path, Format::Json, &json!({"a": 1}), &json_mode(JsonMode::Merge)).unwrap();
+        assert_eq!(parsed(&fs::read_to_string(&path).unwrap()), json!({"a": 1}));
+
+        let path = scratch.path("list.json");
+        save(&path, Format::Json, &json!({"id": 1}), &json_mode(JsonMode::Upsert)).unwrap();
+        assert_eq!(parsed(&fs::read_to_string(&path).unwrap()), json!([{"id": 1}]));
+    }
+}
@@ -0,0 +1,181 @@
+use rmcp::schemars;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use crate::error::{BrowseHandError, Result};
+
+/// What flattening does with arrays.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ArrayMode {
+    /// One cell per column, the items' values joined with the separator.
+    #[default]
+    Join,
+    /// One row per item; several exploded arrays multiply.
+    Explode,
+    /// One column per item: `tags.0`, `tags.1`, ...
+    Index,
+}
+
+/// Turns nested objects into dotted columns (`address.city`).
+#[derive(Debug, Clone)]
+pub struct Flatten {
+    pub arrays: ArrayMode,
+    pub separator: String,
+}
+
+/// A table row: values by column name, keeping the order the columns were
+/// added in (a JSON object would sort them, putting `tags.10` before `tags.2`).
+#[derive(Debug, Clone, Default)]
+pub struct Row {
+    columns: Vec<String>,
+    cells: HashMap<String, Value>,
+}
+
+impl Row {
+    fn single(column: String, value: Value) -> Self {
+        let mut row = Self::default();
+        row.insert(column, value);
+        row
+    }
+
+    fn from_object(obj: &Map<String, Value>) -> Self {
+        let mut row = Self::default();
+        for (key, value) in obj {
+            row.insert(key.clone(), value.clone());
+        }
+        row
+    }
+
+    fn insert(&mut self, column: String, value: Value) {
+        if !self.cells.contains_key(&column) {
+            self.columns.push(column.clone());
+        }
+        self.cells.insert(column, value);
+    }
+
+    fn merge(&mut self, other: &Row) {
+        for column in &other.columns {
+            self.insert(column.clone(), other.cells[column].clone());
+        }
+    }
+
+    /// Drops every column `keep` says no to.
+    pub fn retain(&mut self, keep: impl Fn(&str) -> bool) {
+        self.columns.retain(|c| keep(c));
+        self.cells.retain(|c, _| keep(c));
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn get(&self, column: &str) -> Option<&Value> {
+        self.cells.get(column)
+    }
+}
+
+/// Text of a cell: strings as they are, null as nothing, anything else as JSON.
+pub fn cell(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Rows for `data`, which must be objects. Without `flatten` every top-level
+/// key is a column and nested values stay JSON.
+pub fn rows(data: &[Value], flatten: Option<&Flatten>) -> Result<Vec<Row>> {
+    let mut rows = Vec::with_capacity(data.len());
+    for item in data {
+        let obj = item
+            .as_object()
+            .ok_or_else(|| BrowseHandError::InvalidArgument("each item must be an object".into()))?;
+        match flatten {
+            Some(flatten) => rows.extend(flatten.value("", item)),
+            None => rows.push(Row::from_object(obj)),
+        }
+    }
+    Ok(rows)
+}
+
+fn column(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+impl Flatten {
+    /// Rows for `value` found under the column `prefix`. Only exploded arrays
+    /// give more than one.
+    fn value(&self, prefix: &str, value: &Value) -> Vec<Row> {
+        match value {
+            Value::Object(obj) if !obj.is_empty() => {
+                self.fields(obj.iter().map(|(key, v)| (column(prefix, key), v)))
+            }
+            Value::Array(items) if !items.is_empty() => match self.arrays {
+                ArrayMode::Join => vec![self.joined(prefix, items)],
+                ArrayMode::Explode => items.iter().flat_map(|item| self.value(prefix, item)).collect(),
+                ArrayMode::Index => {
+                    self.fields(items.iter().enumerate().map(|(i, v)| (column(prefix, &i.to_string()), v)))
+                }
+            },
+            // An empty item has no columns at all.
+            Value::Object(_) | Value::Array(_) if prefix.is_empty() => vec![Row::default()],
+            // Keep the column of an empty object or array, blank.
+            Value::Object(_) | Value::Array(_) => vec![Row::single(prefix.to_string(), Value::Null)],
+            scalar => vec![Row::single(prefix.to_string(), scalar.clone())],
+        }
+    }
+
+    /// Combines the rows of each field: every row so far gets every row of the
+    /// next field.
+    fn fields<'a>(&self, fields: impl Iterator<Item = (String, &'a Value)>) -> Vec<Row> {
+        let mut rows = vec![Row::default()];
+        for (name, value) in fields {
+            let parts = self.value(&name, value);
+            rows = rows
+                .iter()
+                .flat_map(|row| {
+                    parts.iter().map(move |part| {
+                        let mut row = row.clone();
+                        row.merge(part);
+                        row
+                    })
+                })
+                .collect();
+        }
+        rows
+    }
+
+    /// Flattens each item on its own and joins each column's values across
+    /// items, so `[{"name": "a"}, {"name": "b"}]` gives `tags.name` = `a; b`.
+    fn joined(&self, prefix: &str, items: &[Value]) -> Row {
+        let mut joined = Row::default();
+        for item in items {
+            for part in self.value(prefix, item) {
+                for name in part.columns() {
+                    let text = cell(&part.cells[name]);
+                    if text.is_empty() {
+                        continue;
+                    }
+                    let value = match joined.get(name) {
+                        Some(Value::String(sofar)) if !sofar.is_empty() => {
+                            format!("{}{}{}", sofar, self.separator, text)
+                        }
+                        _ => text,
+                    };
+                    joined.insert(name.clone(), Value::String(value));
+                }
+            }
+        }
+        if joined.columns().is_empty() {
+            joined.insert(prefix.to_string(), Value::Null);
+        }
+        joined
+    }
+}
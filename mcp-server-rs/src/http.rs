@@ -0,0 +1,62 @@
+use anyhow::{Context, Result};
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use rmcp::transport::streamable_http_server::{
+    session::local::LocalSessionManager, StreamableHttpServerConfig, StreamableHttpService,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tracing::info;
+
+use crate::auth::Pairing;
+use crate::BrowseHand;
+
+pub async fn bind(addr: SocketAddr) -> Result<TcpListener> {
+    TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("cannot listen on {} for MCP over HTTP", addr))
+}
+
+/// Requests must carry the pairing token as `Authorization: Bearer <token>`.
+/// Web pages cannot add that header cross-origin, and local processes need the
+/// token just like the extension does.
+async fn require_token(State(pairing): State<Arc<Pairing>>, request: Request, next: Next) -> Response {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !pairing.verify(token) {
+        return (StatusCode::UNAUTHORIZED, "missing or invalid pairing token").into_response();
+    }
+    next.run(request).await
+}
+
+/// Serves MCP over streamable HTTP (with SSE) at `/mcp` until Ctrl-C. Every
+/// HTTP session gets its own `BrowseHand` client, so several agents can share
+/// one server and one extension connection.
+pub async fn serve(listener: TcpListener, agent: BrowseHand, pairing: Arc<Pairing>) -> Result<()> {
+    let config = StreamableHttpServerConfig::default();
+    let shutdown = config.cancellation_token.clone();
+    let service = StreamableHttpService::new(
+        move || Ok(agent.for_client("http")),
+        Arc::new(LocalSessionManager::default()),
+        config,
+    );
+    let router = Router::new()
+        .nest_service("/mcp", service)
+        .layer(middleware::from_fn_with_state(pairing, require_token));
+
+    info!("MCP over HTTP listening on http://{}/mcp", listener.local_addr()?);
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async move {
+            let _ = tokio::signal::ctrl_c().await;
+            shutdown.cancel();
+        })
+        .await?;
+    Ok(())
+}
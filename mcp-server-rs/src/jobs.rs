@@ -0,0 +1,421 @@
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use cron::Schedule;
+use rmcp::{
+    handler::server::wrapper::Parameters,
+    model::{CallToolResult, Content},
+    schemars,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::error::{BrowseHandError, Result};
+use crate::export;
+use crate::{
+    BrowseHand, ClickArgs, ExecuteScriptArgs, ExtractAllArgs, ExtractArgs, FillFormArgs,
+    NavigateArgs, OpenTabArgs, PressKeysArgs, ReadContentArgs, SaveCsvArgs, SaveDataArgs, SaveJsonArgs,
+    ScreenshotArgs, ScrollArgs, TabIdArgs, TypeTextArgs, WaitArgs,
+};
+use crate::websocket::Target;
+
+/// How often the scheduler checks for due jobs.
+const TICK: Duration = Duration::from_secs(5);
+
+/// One recorded tool call in a job.
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct JobStep {
+    #[schemars(description = "호출할 도구 이름 (예: navigate_to, wait_for_element, extract_structured_data, save_to_csv)")]
+    pub tool: String,
+    #[schemars(description = "도구 인자. save_to_csv/save_to_json/save_data에서 data를 생략하면 직전 추출 결과를 저장합니다")]
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub name: String,
+    pub cron: String,
+    pub steps: Vec<JobStep>,
+    pub created_at: DateTime<Local>,
+    pub last_run_at: Option<DateTime<Local>>,
+    pub last_status: Option<String>,
+}
+
+impl Job {
+    pub fn next_run(&self) -> Option<DateTime<Local>> {
+        parse_cron(&self.cron).ok()?.upcoming(Local).next()
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::json!({
+            "id": self.id,
+            "name": self.name,
+            "cron": self.cron,
+            "steps": self.steps,
+            "nextRun": self.next_run().map(|t| t.to_rfc3339()),
+            "lastRun": self.last_run_at.map(|t| t.to_rfc3339()),
+            "lastStatus": self.last_status,
+        })
+    }
+}
+
+/// Parses a cron expression. Standard 5-field expressions (minute precision)
+/// are accepted alongside the 6/7-field form with seconds.
+pub fn parse_cron(expr: &str) -> Result<Schedule> {
+    let fields = expr.split_whitespace().count();
+    let normalized = if fields == 5 {
+        format!("0 {}", expr.trim())
+    } else {
+        expr.trim().to_string()
+    };
+    Schedule::from_str(&normalized)
+        .map_err(|e| BrowseHandError::InvalidArgument(format!("invalid cron expression '{}': {}", expr, e)))
+}
+
+fn jobs_path() -> PathBuf {
+    crate::config::config_dir().join("jobs.json")
+}
+
+/// Jobs persisted as JSON under the user config directory.
+pub struct JobStore {
+    path: PathBuf,
+    jobs: Mutex<Vec<Job>>,
+}
+
+impl JobStore {
+    /// Reads the saved jobs. A file that cannot be parsed is moved aside to
+    /// `jobs.json.corrupt-<time>` before starting empty, so the next save
+    /// does not overwrite the user's jobs; one that cannot be read at all
+    /// stops the server.
+    pub fn load() -> anyhow::Result<Self> {
+        let path = jobs_path();
+        let jobs = match std::fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(jobs) => jobs,
+                Err(e) => {
+                    let backup = path.with_extension(format!("json.corrupt-{}", Local::now().format("%Y%m%d%H%M%S")));
+                    std::fs::rename(&path, &backup).with_context(|| {
+                        format!("job file {} is invalid ({}) and could not be moved aside", path.display(), e)
+                    })?;
+                    warn!(
+                        "Job file {} is invalid ({}); moved it to {} and starting with no jobs",
+                        path.display(),
+                        e,
+                        backup.display()
+                    );
+                    Vec::new()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).with_context(|| format!("cannot read job file {}", path.display())),
+        };
+        Ok(Self {
+            path,
+            jobs: Mutex::new(jobs),
+        })
+    }
+
+    fn persist(&self, jobs: &[Job]) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let content = serde_json::to_vec_pretty(jobs).map_err(|e| BrowseHandError::FileIo(e.into()))?;
+        export::write_atomic(&self.path, &content)
+    }
+
+    pub async fn list(&self) -> Vec<Job> {
+        self.jobs.lock().await.clone()
+    }
+
+    /// Looks a job up by id, falling back to its name.
+    pub async fn find(&self, key: &str) -> Option<Job> {
+        let jobs = self.jobs.lock().await;
+        jobs.iter()
+            .find(|j| j.id == key)
+            .or_else(|| jobs.iter().find(|j| j.name == key))
+            .cloned()
+    }
+
+    pub async fn add(&self, name: String, cron: String, steps: Vec<JobStep>) -> Result<Job> {
+        parse_cron(&cron)?;
+        if steps.is_empty() {
+            return Err(BrowseHandError::InvalidArgument("a job needs at least one step".into()));
+        }
+        for step in &steps {
+            validate_step(step)?;
+        }
+
+        let mut jobs = self.jobs.lock().await;
+        if jobs.iter().any(|j| j.name == name) {
+            return Err(BrowseHandError::InvalidArgument(format!("a job named '{}' already exists", name)));
+        }
+        let job = Job {
+            id: uuid::Uuid::new_v4().simple().to_string()[..8].to_string(),
+            name,
+            cron,
+            steps,
+            created_at: Local::now(),
+            last_run_at: None,
+            last_status: None,
+        };
+        jobs.push(job.clone());
+        self.persist(&jobs)?;
+        Ok(job)
+    }
+
+    pub async fn remove(&self, key: &str) -> Result<Option<Job>> {
+        let mut jobs = self.jobs.lock().await;
+        let Some(index) = jobs.iter().position(|j| j.id == key || j.name == key) else {
+            return Ok(None);
+        };
+        let job = jobs.remove(index);
+        self.persist(&jobs)?;
+        Ok(Some(job))
+    }
+
+    async fn record_run(&self, id: &str, at: DateTime<Local>, status: String) {
+        let mut jobs = self.jobs.lock().await;
+        if let Some(job) = jobs.iter_mut().find(|j| j.id == id) {
+            job.last_run_at = Some(at);
+            job.last_status = Some(status);
+        }
+        if let Err(e) = self.persist(&jobs) {
+            error!("Failed to save job state: {}", e);
+        }
+    }
+}
+
+fn parse<T: DeserializeOwned>(tool: &str, arguments: Value) -> Result<Parameters<T>> {
+    serde_json::from_value(arguments)
+        .map(Parameters)
+        .map_err(|e| BrowseHandError::InvalidArgument(format!("{}: {}", tool, e)))
+}
+
+/// Arguments for a step. Save steps may leave out `data` to store whatever the
+/// previous step extracted.
+fn step_arguments(step: &JobStep, data: Option<&Value>) -> Value {
+    let mut arguments = if step.arguments.is_null() {
+        serde_json::json!({})
+    } else {
+        step.arguments.clone()
+    };
+    if matches!(step.tool.as_str(), "save_to_csv" | "save_to_json" | "save_data") {
+        if let Some(obj) = arguments.as_object_mut() {
+            if !obj.contains_key("data") {
+                obj.insert("data".into(), data.cloned().unwrap_or(Value::Array(Vec::new())));
+            }
+        }
+    }
+    arguments
+}
+
+// Tools a job step may call, with their argument types. Job management tools
+// and wait_for_human are deliberately absent.
+macro_rules! job_tools {
+    (
+        async { $($tool:ident: $args:ty),* $(,)? }
+        sync { $($sync_tool:ident: $sync_args:ty),* $(,)? }
+    ) => {
+        pub const JOB_TOOLS: &[&str] = &[$(stringify!($tool),)* $(stringify!($sync_tool)),*];
+
+        fn validate_step(step: &JobStep) -> Result<()> {
+            let arguments = step_arguments(step, None);
+            match step.tool.as_str() {
+                $(stringify!($tool) => parse::<$args>(&step.tool, arguments).map(|_| ()),)*
+                $(stringify!($sync_tool) => parse::<$sync_args>(&step.tool, arguments).map(|_| ()),)*
+                other => Err(BrowseHandError::InvalidArgument(format!(
+                    "tool '{}' cannot be used in a job (allowed: {})",
+                    other,
+                    JOB_TOOLS.join(", ")
+                ))),
+            }
+        }
+
+        impl BrowseHand {
+            async fn run_step(
+                &self,
+                step: &JobStep,
+                data: Option<&Value>,
+                ct: &CancellationToken,
+            ) -> Result<CallToolResult> {
+                let arguments = step_arguments(step, data);
+                let result = match step.tool.as_str() {
+                    $(stringify!($tool) => self.$tool(parse::<$args>(&step.tool, arguments)?, ct.clone()).await,)*
+                    $(stringify!($sync_tool) => self.$sync_tool(parse::<$sync_args>(&step.tool, arguments)?),)*
+                    other => return Err(BrowseHandError::InvalidArgument(format!("unknown tool: {}", other))),
+                };
+                result.map_err(|e| BrowseHandError::Extension(e.message.to_string()))
+            }
+        }
+    };
+}
+
+job_tools! {
+    async {
+        navigate_to: NavigateArgs,
+        click_element: ClickArgs,
+        scroll_page: ScrollArgs,
+        wait_for_element: WaitArgs,
+        read_browser_content: ReadContentArgs,
+        execute_script: ExecuteScriptArgs,
+        extract_structured_data: ExtractArgs,
+        extract_all_pages: ExtractAllArgs,
+        fill_form: FillFormArgs,
+        type_text: TypeTextArgs,
+        press_keys: PressKeysArgs,
+        get_current_url: Target,
+        take_screenshot: ScreenshotArgs,
+        open_tab: OpenTabArgs,
+        close_tab: TabIdArgs,
+        activate_tab: TabIdArgs,
+    }
+    sync {
+        save_to_csv: SaveCsvArgs,
+        save_to_json: SaveJsonArgs,
+        save_data: SaveDataArgs,
+    }
+}
+
+fn result_text(result: &CallToolResult) -> String {
+    result
+        .content
+        .iter()
+        .filter_map(|c| c.as_text().map(|t| t.text.clone()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// How a job run ended.
+pub(crate) enum JobRun {
+    Finished { steps: usize },
+    Cancelled { step: usize, tool: String },
+    /// `result` is the failing step's own error result, with its error code.
+    Failed { step: usize, tool: String, result: CallToolResult },
+}
+
+impl JobRun {
+    /// The status line stored as the job's `lastStatus`.
+    pub fn status(&self) -> String {
+        match self {
+            JobRun::Finished { steps } => format!("ok ({} steps)", steps),
+            JobRun::Cancelled { step, tool } => format!("cancelled before step {} ({})", step, tool),
+            JobRun::Failed { step, tool, result } => {
+                format!("failed at step {} ({}): {}", step, tool, result_text(result))
+            }
+        }
+    }
+
+    /// The outcome as a tool result. A failure keeps the step's error code
+    /// and says which step it came from.
+    pub fn into_result(self, job: &Job) -> CallToolResult {
+        let status = self.status();
+        match self {
+            JobRun::Finished { .. } => CallToolResult::success(vec![Content::text(format!(
+                "✅ Job '{}' finished: {}",
+                job.name, status
+            ))]),
+            JobRun::Cancelled { .. } => BrowseHandError::Cancelled.into(),
+            JobRun::Failed { step, tool, mut result } => {
+                result
+                    .content
+                    .insert(0, Content::text(format!("Job '{}' failed at step {} ({}):", job.name, step, tool)));
+                if let Some(error) = result
+                    .structured_content
+                    .as_mut()
+                    .and_then(|s| s.get_mut("error"))
+                    .and_then(Value::as_object_mut)
+                {
+                    error.insert("job".into(), serde_json::json!(job.id));
+                    error.insert("step".into(), serde_json::json!(step));
+                    error.insert("tool".into(), serde_json::json!(tool));
+                }
+                result
+            }
+        }
+    }
+}
+
+impl BrowseHand {
+    /// Runs every step of a job in order, stopping at the first failure or
+    /// when `ct` is cancelled, and records the outcome.
+    pub(crate) async fn run_job(&self, job: &Job, ct: CancellationToken) -> JobRun {
+        let started = Local::now();
+        let mut data: Option<Value> = None;
+        let mut run = JobRun::Finished { steps: job.steps.len() };
+
+        for (i, step) in job.steps.iter().enumerate() {
+            if ct.is_cancelled() {
+                run = JobRun::Cancelled { step: i + 1, tool: step.tool.clone() };
+                break;
+            }
+            let result = match self.run_step(step, data.as_ref(), &ct).await {
+                Ok(result) => result,
+                Err(e) => e.into(),
+            };
+            if result.is_error == Some(true) {
+                run = JobRun::Failed { step: i + 1, tool: step.tool.clone(), result };
+                break;
+            }
+            if let Some(d) = result.structured_content.as_ref().and_then(|s| s.get("data")) {
+                data = Some(d.clone());
+            }
+        }
+
+        let status = run.status();
+        info!("Job '{}' finished: {}", job.name, status);
+        self.jobs.record_run(&job.id, started, status).await;
+        run
+    }
+}
+
+/// Fires jobs on their cron schedule. A job that comes due while no extension
+/// is connected waits and runs as soon as one connects.
+pub async fn run_scheduler(agent: BrowseHand) {
+    let mut next_runs: HashMap<String, DateTime<Local>> = HashMap::new();
+    let running: Arc<Mutex<HashSet<String>>> = Arc::new(Mutex::new(HashSet::new()));
+    let mut interval = tokio::time::interval(TICK);
+
+    loop {
+        interval.tick().await;
+        let now = Local::now();
+        let jobs = agent.jobs.list().await;
+        next_runs.retain(|id, _| jobs.iter().any(|j| &j.id == id));
+
+        for job in jobs {
+            let Some(due) = next_runs.get(&job.id).copied().or_else(|| job.next_run()) else {
+                continue;
+            };
+            next_runs.insert(job.id.clone(), due);
+            if due > now {
+                continue;
+            }
+            if !agent.bridge.read().await.is_connected() {
+                continue;
+            }
+            if !running.lock().await.insert(job.id.clone()) {
+                continue;
+            }
+
+            if let Some(next) = parse_cron(&job.cron).ok().and_then(|s| s.after(&now).next()) {
+                next_runs.insert(job.id.clone(), next);
+            }
+
+            let agent = agent.clone();
+            let running = Arc::clone(&running);
+            tokio::spawn(async move {
+                info!("Running scheduled job '{}'", job.name);
+                agent.run_job(&job, CancellationToken::new()).await;
+                running.lock().await.remove(&job.id);
+            });
+        }
+    }
+}
@@ -1,32 +1,95 @@
+mod auth;
+mod challenge;
+mod cli;
+mod config;
+mod error;
+mod events;
+mod export;
+mod flatten;
+mod http;
+mod jobs;
+mod paging;
+mod protocol;
+mod queue;
+mod tls;
 mod tools;
 mod websocket;
 
 use anyhow::Result;
+use clap::Parser;
+use cli::{Cli, Command, ConfigCommand};
 use rmcp::{
-    ErrorData as McpError, ServerHandler, ServiceExt,
+    ErrorData as McpError, RoleServer, ServerHandler, ServiceExt,
     handler::server::{router::tool::ToolRouter, wrapper::Parameters},
     model::*,
+    service::RequestContext,
     schemars,
     tool, tool_handler, tool_router,
     transport::stdio,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
+use std::process::ExitCode;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::io::DuplexStream;
+use tokio::sync::{mpsc, RwLock};
+use tokio_util::sync::CancellationToken;
 use tracing::{error, info};
-use websocket::ExtensionBridge;
+use uuid::Uuid;
+use config::Config;
+use error::BrowseHandError;
+use jobs::{JobStep, JobStore};
+use events::EventFilter;
+use export::{Format, JsonMode, SaveOptions};
+use flatten::{ArrayMode, Flatten};
+use protocol::{EventKind, PROTOCOL_VERSION};
+use tools::OutputDir;
+use websocket::{Caller, ExtensionBridge, Target};
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct ReadContentArgs {
     #[schemars(description = "추출할 DOM 셀렉터 (선택사항, 기본값: body)")]
     pub selector: Option<String>,
+    #[serde(flatten)]
+    pub target: Target,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct ExecuteScriptArgs {
     #[schemars(description = "실행할 JavaScript 코드")]
     pub code: String,
+    #[serde(flatten)]
+    pub target: Target,
+}
+
+/// How rows become a table, shared by the tabular save tools.
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct TableArgs {
+    #[schemars(description = "true면 기존 파일에 추가. csv/tsv/markdown은 기존 헤더에 맞추고 새 열이 있으면 헤더를 넓혀 다시 씀, jsonl은 줄을 추가, sqlite는 테이블에 행을 추가 (xlsx/parquet/json은 지원하지 않음)")]
+    pub append: Option<bool>,
+    #[schemars(description = "저장할 열과 순서 (생략하면 모든 행의 키: 열은 처음 나온 행 순서대로, 각 행 안에서는 키 알파벳 순)")]
+    pub columns: Option<Vec<String>>,
+    #[schemars(description = "true면 중첩 객체/배열을 점 표기 열로 펼침 (예: address.city)")]
+    pub flatten: Option<bool>,
+    #[schemars(description = "flatten 시 배열 처리: join(구분자로 합침, 기본값), explode(요소마다 행을 나눔), index(tags.0, tags.1 열)")]
+    pub arrays: Option<ArrayMode>,
+    #[schemars(description = "arrays가 join일 때 구분자 (기본값: \"; \")")]
+    pub separator: Option<String>,
+}
+
+impl TableArgs {
+    fn options(&self) -> SaveOptions {
+        SaveOptions {
+            append: self.append.unwrap_or(false),
+            columns: self.columns.clone(),
+            flatten: self.flatten.unwrap_or(false).then(|| Flatten {
+                arrays: self.arrays.unwrap_or_default(),
+                separator: self.separator.clone().unwrap_or_else(|| "; ".to_string()),
+            }),
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -35,8 +98,22 @@ pub struct SaveCsvArgs {
     pub filename: String,
     #[schemars(description = "저장할 데이터 배열")]
     pub data: Value,
-    #[schemars(description = "true면 기존 파일에 추가")]
-    pub append: Option<bool>,
+    #[serde(flatten)]
+    pub options: TableArgs,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SaveDataArgs {
+    #[schemars(description = "저장할 파일명 (예: leads.xlsx)")]
+    pub filename: String,
+    #[schemars(description = "저장할 데이터 배열")]
+    pub data: Value,
+    #[schemars(description = "파일 형식: csv, tsv, json, jsonl, xlsx, parquet, sqlite, markdown (생략하면 파일 확장자로 판단)")]
+    pub format: Option<Format>,
+    #[schemars(description = "sqlite 형식에서 저장할 테이블 이름 (기본값: data)")]
+    pub table: Option<String>,
+    #[serde(flatten)]
+    pub options: TableArgs,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -45,14 +122,30 @@ pub struct SaveJsonArgs {
     pub filename: String,
     #[schemars(description = "저장할 데이터")]
     pub data: Value,
+    #[schemars(description = "기존 파일과 합치는 방식: overwrite(덮어쓰기, 기본값), append(배열 끝에 추가), merge(객체에 병합, 중첩 객체까지), upsert(key가 같은 항목은 교체하고 나머지는 추가)")]
+    pub mode: Option<JsonMode>,
+    #[schemars(description = "upsert에서 항목을 구분할 필드 (예: url)")]
+    pub key: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct ScrollArgs {
-    #[schemars(description = "스크롤 방향: down, up, bottom, top")]
-    pub direction: String,
+    #[schemars(description = "스크롤 방향: down, up, bottom, top (기본값: down)")]
+    pub direction: Option<String>,
     #[schemars(description = "스크롤할 픽셀 수 (기본값: 500)")]
     pub amount: Option<i32>,
+    #[schemars(description = "스크롤할 컨테이너 셀렉터 (예: 'div[role=\"feed\"]', 생략하면 페이지 전체)")]
+    pub selector: Option<String>,
+    #[schemars(description = "이 셀렉터의 요소가 보이도록 스크롤 (지정하면 direction은 무시)")]
+    pub into_view: Option<String>,
+    #[schemars(description = "true면 콘텐츠가 더 늘어나지 않을 때까지 맨 아래로 반복 스크롤 (무한 스크롤 목록용)")]
+    pub until_stable: Option<bool>,
+    #[schemars(description = "until_stable에서 매 스크롤 후 대기 시간 (밀리초, 기본값: 1000)")]
+    pub settle_ms: Option<u64>,
+    #[schemars(description = "until_stable 최대 스크롤 횟수 (기본값: 20)")]
+    pub max_rounds: Option<u64>,
+    #[serde(flatten)]
+    pub target: Target,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -61,6 +154,62 @@ pub struct ClickArgs {
     pub selector: String,
     #[schemars(description = "클릭 후 대기할 밀리초 (기본값: 1000)")]
     pub wait_after: Option<i32>,
+    #[serde(flatten)]
+    pub target: Target,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    #[default]
+    Text,
+    Select,
+    Checkbox,
+    Radio,
+    Date,
+    File,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct FormField {
+    #[schemars(description = "필드 종류: text, select, checkbox, radio, date, file (기본값: text)")]
+    pub kind: Option<FieldKind>,
+    #[schemars(description = "입력할 값. select는 option의 value 또는 표시 텍스트, checkbox는 true/false, radio는 선택할 value, date는 YYYY-MM-DD, file은 출력 폴더 기준 파일 경로")]
+    pub value: Value,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct FillFormArgs {
+    #[schemars(description = "CSS 셀렉터 → 필드 정의 맵 (예: {\"#name\": {\"kind\": \"text\", \"value\": \"홍길동\"}, \"#agree\": {\"kind\": \"checkbox\", \"value\": true}})")]
+    pub fields: BTreeMap<String, FormField>,
+    #[schemars(description = "모든 필드 입력 후 클릭할 제출 버튼 셀렉터 (선택사항)")]
+    pub submit_selector: Option<String>,
+    #[serde(flatten)]
+    pub target: Target,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct TypeTextArgs {
+    #[schemars(description = "입력할 텍스트")]
+    pub text: String,
+    #[schemars(description = "입력할 요소의 CSS 셀렉터 (선택사항, 기본값: 현재 포커스된 요소)")]
+    pub selector: Option<String>,
+    #[schemars(description = "키 입력 사이의 지연 시간(밀리초, 기본값: 0)")]
+    pub delay_ms: Option<u32>,
+    #[schemars(description = "입력 전에 기존 값을 지울지 여부 (기본값: false)")]
+    pub clear: Option<bool>,
+    #[serde(flatten)]
+    pub target: Target,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct PressKeysArgs {
+    #[schemars(description = "순서대로 누를 키 또는 조합키 목록 (예: [\"Control+A\", \"Backspace\", \"Enter\", \"Tab\"])")]
+    pub keys: Vec<String>,
+    #[schemars(description = "키를 보낼 요소의 CSS 셀렉터 (선택사항, 기본값: 현재 포커스된 요소)")]
+    pub selector: Option<String>,
+    #[serde(flatten)]
+    pub target: Target,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -69,6 +218,18 @@ pub struct WaitArgs {
     pub selector: String,
     #[schemars(description = "최대 대기 시간(밀리초, 기본값: 10000)")]
     pub timeout: Option<i32>,
+    #[serde(flatten)]
+    pub target: Target,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct WaitForHumanArgs {
+    #[schemars(description = "최대 대기 시간(초, 기본값: 300)")]
+    pub timeout_secs: Option<u64>,
+    #[schemars(description = "해결 여부를 확인하는 간격(밀리초, 기본값: 2000)")]
+    pub poll_interval_ms: Option<u64>,
+    #[serde(flatten)]
+    pub target: Target,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -79,47 +240,243 @@ pub struct ExtractArgs {
     pub fields: Value,
     #[schemars(description = "최대 추출 개수")]
     pub limit: Option<i32>,
+    #[serde(flatten)]
+    pub target: Target,
+}
+
+impl ExtractArgs {
+    fn request(&self) -> protocol::ExtractStructuredData {
+        protocol::ExtractStructuredData {
+            container_selector: self.container_selector.clone(),
+            fields: self.fields.clone(),
+            limit: self.limit,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExtractAllArgs {
+    #[serde(flatten)]
+    pub extract: ExtractArgs,
+    #[schemars(description = "다음 페이지 버튼 셀렉터 (페이지네이션 방식일 때)")]
+    pub next_selector: Option<String>,
+    #[schemars(description = "무한 스크롤 컨테이너 셀렉터 (next_selector가 없을 때 사용, 생략하면 페이지 전체를 스크롤)")]
+    pub scroll_container: Option<String>,
+    #[schemars(description = "목표 항목 수 (도달하면 중단)")]
+    pub target_count: Option<usize>,
+    #[schemars(description = "최대 반복 횟수 (기본값: 20)")]
+    pub max_iterations: Option<u32>,
+    #[schemars(description = "중복 제거 기준 필드명 (기본값: 행 전체 비교)")]
+    pub dedupe_key: Option<String>,
+    #[schemars(description = "페이지 이동/스크롤 후 대기 시간 (밀리초, 기본값: 1500)")]
+    pub wait_ms: Option<u64>,
+    #[schemars(description = "결과를 페이지마다 바로 저장할 CSV 파일명 (선택사항)")]
+    pub save_to: Option<String>,
+    #[schemars(description = "true면 save_to 파일에 이어서 추가")]
+    pub append: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct NavigateArgs {
     #[schemars(description = "이동할 URL")]
     pub url: String,
+    #[serde(flatten)]
+    pub target: Target,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreenshotMode {
+    #[default]
+    Viewport,
+    FullPage,
+    Element,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Jpeg,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ScreenshotArgs {
+    #[schemars(description = "캡처 범위: viewport(보이는 영역), full_page(전체 페이지), element(특정 요소) (기본값: viewport)")]
+    pub mode: Option<ScreenshotMode>,
+    #[schemars(description = "element 모드에서 캡처할 요소의 CSS 셀렉터")]
+    pub selector: Option<String>,
+    #[schemars(description = "이미지 형식: png, jpeg (기본값: png)")]
+    pub format: Option<ImageFormat>,
+    #[schemars(description = "JPEG 품질 0-100 (기본값: 80)")]
+    pub quality: Option<u8>,
+    #[schemars(description = "최대 너비(픽셀). 더 넓으면 비율을 유지하며 축소합니다")]
+    pub max_width: Option<u32>,
+    #[schemars(description = "이미지를 저장할 파일명 (선택사항, 출력 폴더 기준 상대 경로)")]
+    pub save_to: Option<String>,
+    #[serde(flatten)]
+    pub target: Target,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct BrowserArgs {
+    #[schemars(description = "대상 브라우저 세션 이름 (list_browsers로 확인, 하나만 연결된 경우 생략 가능)")]
+    pub browser: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ListTabsArgs {
+    #[schemars(description = "특정 창의 탭만 조회할 창 ID (선택사항, 기본값: 모든 창)")]
+    pub window_id: Option<i64>,
+    #[schemars(description = "대상 브라우저 세션 이름 (list_browsers로 확인, 하나만 연결된 경우 생략 가능)")]
+    pub browser: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct OpenTabArgs {
+    #[schemars(description = "새 탭에서 열 URL (선택사항, 기본값: 빈 탭)")]
+    pub url: Option<String>,
+    #[schemars(description = "새 탭을 활성화할지 여부 (기본값: true)")]
+    pub active: Option<bool>,
+    #[schemars(description = "탭을 열 창 ID (선택사항, 기본값: 현재 창)")]
+    pub window_id: Option<i64>,
+    #[schemars(description = "대상 브라우저 세션 이름 (list_browsers로 확인, 하나만 연결된 경우 생략 가능)")]
+    pub browser: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct TabIdArgs {
+    #[schemars(description = "대상 탭 ID (list_tabs로 확인)")]
+    pub tab_id: i64,
+    #[schemars(description = "대상 브라우저 세션 이름 (list_browsers로 확인, 하나만 연결된 경우 생략 가능)")]
+    pub browser: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct GetEventsArgs {
+    #[schemars(description = "이전 get_events 결과의 cursor. 지정하면 그 이후에 발생한 이벤트만 오래된 순으로 가져옵니다 (생략하면 최근 이벤트)")]
+    pub since: Option<u64>,
+    #[schemars(description = "가져올 이벤트 종류: navigation, load, console_error, dialog, download (생략하면 전체)")]
+    pub kinds: Option<Vec<EventKind>>,
+    #[schemars(description = "특정 탭의 이벤트만 조회할 탭 ID (선택사항)")]
+    pub tab_id: Option<i64>,
+    #[schemars(description = "대상 브라우저 세션 이름 (선택사항, 기본값: 모든 브라우저)")]
+    pub browser: Option<String>,
+    #[schemars(description = "최대 이벤트 수 (기본값: 50)")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ScheduleJobArgs {
+    #[schemars(description = "작업 이름 (고유해야 함)")]
+    pub name: String,
+    #[schemars(description = "cron 표현식 (예: '0 9 * * *' 매일 오전 9시, '*/30 * * * *' 30분마다). 초 단위 6필드 형식도 허용")]
+    pub cron: String,
+    #[schemars(description = "순서대로 실행할 도구 호출 목록")]
+    pub steps: Vec<JobStep>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct JobIdArgs {
+    #[schemars(description = "작업 ID 또는 이름 (list_jobs로 확인)")]
+    pub job: String,
+}
+
+/// Upper bound on one `scroll_page` run with `until_stable`.
+const MAX_STABLE_SCROLL_MS: u64 = 25_000;
+
+const DEFAULT_EVENT_LIMIT: usize = 50;
+
+/// MCP resource with the events in the log.
+const EVENTS_URI: &str = "browsehand://events";
+
+/// One MCP client (the stdio peer, an HTTP session, a CLI call or the job
+/// scheduler). Its id tags the bridge requests it issues; when the last handle
+/// goes away, whatever it still had pending is dropped.
+struct ClientSession {
+    id: String,
+    bridge: Arc<RwLock<ExtensionBridge>>,
+}
+
+impl Drop for ClientSession {
+    fn drop(&mut self) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let id = std::mem::take(&mut self.id);
+        let bridge = Arc::clone(&self.bridge);
+        runtime.spawn(async move {
+            let dropped = bridge.read().await.release_client(&id).await;
+            if dropped > 0 {
+                info!("Dropped {} pending requests of closed client {}", dropped, id);
+            }
+        });
+    }
 }
 
 #[derive(Clone)]
 pub struct BrowseHand {
     bridge: Arc<RwLock<ExtensionBridge>>,
+    jobs: Arc<JobStore>,
+    output: Arc<OutputDir>,
+    client: Arc<ClientSession>,
     tool_router: ToolRouter<BrowseHand>,
 }
 
 #[tool_router]
 impl BrowseHand {
-    pub fn new(bridge: Arc<RwLock<ExtensionBridge>>) -> Self {
+    pub fn new(bridge: Arc<RwLock<ExtensionBridge>>, jobs: Arc<JobStore>, output: Arc<OutputDir>) -> Self {
         Self {
+            client: Arc::new(ClientSession {
+                id: "stdio".to_string(),
+                bridge: Arc::clone(&bridge),
+            }),
             bridge,
+            jobs,
+            output,
             tool_router: Self::tool_router(),
         }
     }
 
-    #[tool(description = "현재 활성화된 브라우저 탭의 HTML 콘텐츠를 읽어옵니다.")]
+    /// A handle for another MCP client, sharing the bridge and job store but
+    /// with its own pending requests.
+    pub fn for_client(&self, kind: &str) -> Self {
+        Self {
+            client: Arc::new(ClientSession {
+                id: format!("{}-{}", kind, &Uuid::new_v4().simple().to_string()[..8]),
+                bridge: Arc::clone(&self.bridge),
+            }),
+            ..self.clone()
+        }
+    }
+
+    /// Bridge requests made on behalf of one tool call, aborted when `ct` is
+    /// cancelled by the MCP client.
+    fn caller(&self, ct: CancellationToken) -> Caller {
+        Caller {
+            client: self.client.id.clone(),
+            cancel: ct,
+        }
+    }
+
+    #[tool(description = "브라우저 탭의 HTML 콘텐츠를 읽어옵니다. tab_id를 지정하지 않으면 현재 활성 탭을 사용합니다.")]
     async fn read_browser_content(
         &self,
         Parameters(args): Parameters<ReadContentArgs>,
+        ct: CancellationToken,
     ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
         let selector = args.selector.unwrap_or_else(|| "body".to_string());
         let bridge = self.bridge.read().await;
         
-        match bridge
-            .send_and_wait("read_content", serde_json::json!({ "selector": selector }))
-            .await
-        {
-            Ok(response) => Ok(CallToolResult::success(vec![Content::text(format!(
+        let request = protocol::ReadContent { selector: selector.clone() };
+        match bridge.send_and_wait(&caller, request, &args.target).await {
+            Ok(reply) => Ok(CallToolResult::success(vec![Content::text(format!(
                 "Content from \"{}\":\n\n{}",
-                selector,
-                response["data"].as_str().unwrap_or("")
+                selector, reply.data
             ))])),
-            Err(e) => Ok(CallToolResult::success(vec![Content::text(format!("Error: {}", e))])),
+            Err(e) => Ok(e.into()),
         }
     }
 
@@ -127,67 +484,204 @@ impl BrowseHand {
     async fn execute_script(
         &self,
         Parameters(args): Parameters<ExecuteScriptArgs>,
+        ct: CancellationToken,
     ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
         let bridge = self.bridge.read().await;
         
         match bridge
-            .send_and_wait("execute_script", serde_json::json!({ "code": args.code }))
+            .send_and_wait(&caller, protocol::ExecuteScript { code: args.code }, &args.target)
             .await
         {
-            Ok(response) => Ok(CallToolResult::success(vec![Content::text(format!(
+            Ok(reply) => Ok(CallToolResult::success(vec![Content::text(format!(
                 "Script executed. Result:\n{}",
-                serde_json::to_string_pretty(&response["result"]).unwrap_or_default()
+                serde_json::to_string_pretty(&reply.result).unwrap_or_default()
             ))])),
-            Err(e) => Ok(CallToolResult::success(vec![Content::text(format!("Error: {}", e))])),
+            Err(e) => Ok(e.into()),
         }
     }
 
-    #[tool(description = "Chrome Extension과의 연결 상태를 확인합니다.")]
-    async fn ping_extension(&self) -> Result<CallToolResult, McpError> {
+    #[tool(description = "Chrome Extension과의 연결 상태를 확인합니다. 응답 지연 시간, 연결 유지 시간, 확장 버전, 활성 탭 URL을 함께 반환합니다.")]
+    async fn ping_extension(
+        &self,
+        Parameters(args): Parameters<BrowserArgs>,
+        ct: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
         let bridge = self.bridge.read().await;
 
-        if bridge.is_connected() {
-            match bridge.send_and_wait("ping", serde_json::json!({})).await {
-                Ok(_) => Ok(CallToolResult::success(vec![Content::text(
-                    "✅ Chrome Extension is connected and responding.",
-                )])),
-                Err(e) => Ok(CallToolResult::success(vec![Content::text(format!(
-                    "⚠️ Connected but not responding: {}",
-                    e
-                ))])),
+        let connected = match &args.browser {
+            Some(name) => bridge.has_session(name),
+            None => bridge.is_connected(),
+        };
+
+        if connected {
+            let target = Target {
+                tab_id: None,
+                browser: args.browser,
+            };
+            let sent = std::time::Instant::now();
+            let reply = match bridge.send_and_wait(&caller, protocol::Ping {}, &target).await {
+                Ok(reply) => reply,
+                Err(e) => return Ok(e.into()),
+            };
+            let round_trip = sent.elapsed().as_millis() as u64;
+            let mut health = match bridge.describe_session(target.browser.as_deref()) {
+                Ok(health) => health,
+                Err(e) => return Ok(e.into()),
+            };
+            health["requestLatencyMs"] = serde_json::json!(round_trip);
+            health["activeTab"] = serde_json::json!(reply.active_tab);
+            health["protocol"] = serde_json::json!(PROTOCOL_VERSION);
+            if health["extensionVersion"].is_null() {
+                health["extensionVersion"] = serde_json::json!(reply.version);
             }
+            
+            let heartbeat = match health["latencyMs"].as_u64() {
+                Some(ms) => format!(", heartbeat {}ms", ms),
+                None => String::new(),
+            };
+            let mut result = CallToolResult::success(vec![Content::text(format!(
+                "✅ Chrome Extension is connected and responding.\nBrowser: {} (extension {})\nLatency: {}ms{}\nConnected for {}s\nActive tab: {}",
+                health["name"].as_str().unwrap_or("?"),
+                health["extensionVersion"].as_str().unwrap_or("unknown"),
+                round_trip,
+                heartbeat,
+                health["connectedSecs"],
+                health["activeTab"]["url"].as_str().unwrap_or("none")
+            ))]);
+            result.structured_content = Some(health);
+            Ok(result)
         } else {
-            Ok(CallToolResult::success(vec![Content::text(
-                "❌ Chrome Extension is not connected.",
-            )]))
+            match args.browser {
+                Some(name) => Ok(BrowseHandError::BrowserNotFound(name).into()),
+                None => Ok(BrowseHandError::NotConnected.into()),
+            }
         }
     }
 
-    #[tool(description = "데이터를 CSV 파일로 저장합니다. 경로를 지정하지 않으면 바탕화면에 저장됩니다.")]
+    #[tool(description = "연결된 브라우저 세션 목록(세션 이름, 주소, 연결 시간, 확장 버전)을 가져옵니다.")]
+    async fn list_browsers(&self) -> Result<CallToolResult, McpError> {
+        let bridge = self.bridge.read().await;
+        let sessions = bridge.list_sessions();
+        
+        let mut result = if sessions.is_empty() {
+            CallToolResult::success(vec![Content::text("No browsers connected.")])
+        } else {
+            CallToolResult::success(vec![Content::text(format!(
+                "Connected browsers ({}):\n{}",
+                sessions.len(),
+                serde_json::to_string_pretty(&sessions).unwrap_or_default()
+            ))])
+        };
+        result.structured_content = Some(serde_json::json!({ "browsers": sessions }));
+        Ok(result)
+    }
+
+    #[tool(description = "브라우저별 동시 실행 중인 요청 수와 탭별 요청 대기열(실행 중인 요청, 대기 중인 요청 수)을 가져옵니다. 같은 탭에 대한 요청은 순서대로 하나씩 실행됩니다.")]
+    async fn get_queue_status(
+        &self,
+        Parameters(args): Parameters<BrowserArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let bridge = self.bridge.read().await;
+        if let Some(name) = &args.browser {
+            if !bridge.has_session(name) {
+                return Ok(BrowseHandError::BrowserNotFound(name.clone()).into());
+            }
+        }
+        let status = bridge.queue_status(args.browser.as_deref());
+        
+        let busy = status["tabs"].as_array().map_or(0, Vec::len);
+        let mut result = CallToolResult::success(vec![Content::text(format!(
+            "{}:\n{}",
+            if busy == 0 { "No queued requests".to_string() } else { format!("Busy tabs ({})", busy) },
+            serde_json::to_string_pretty(&status).unwrap_or_default()
+        ))]);
+        result.structured_content = Some(status);
+        Ok(result)
+    }
+
+    #[tool(description = "브라우저에서 일어난 이벤트(탭 이동, 페이지 로드 완료, 콘솔 오류, alert/confirm/prompt 대화상자, 다운로드)를 가져옵니다. 결과의 cursor를 다음 호출의 since로 넘기면 그 사이에 새로 일어난 일만 받을 수 있습니다.")]
+    async fn get_events(
+        &self,
+        Parameters(args): Parameters<GetEventsArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let bridge = self.bridge.read().await;
+        let kinds = args.kinds.unwrap_or_default();
+        let page = bridge.events().query(&EventFilter {
+            since: args.since,
+            browser: args.browser.as_deref(),
+            tab_id: args.tab_id,
+            kinds: &kinds,
+            limit: args.limit.unwrap_or(DEFAULT_EVENT_LIMIT).max(1),
+        });
+        
+        let mut text = if page.events.is_empty() {
+            format!("No {}events (cursor {})", if args.since.is_some() { "new " } else { "" }, page.cursor)
+        } else {
+            format!(
+                "Events ({}, cursor {}):\n{}",
+                page.events.len(),
+                page.cursor,
+                serde_json::to_string_pretty(&page.events).unwrap_or_default()
+            )
+        };
+        if page.missed > 0 {
+            text.push_str(&format!("\n⚠️ {} older events were dropped before they were read", page.missed));
+        }
+        let mut result = CallToolResult::success(vec![Content::text(text)]);
+        result.structured_content = Some(page.to_json());
+        Ok(result)
+    }
+
+    #[tool(description = "데이터를 CSV 파일로 저장합니다. 파일은 출력 폴더(기본값: 바탕화면의 BrowseHand) 안에 저장됩니다.")]
     fn save_to_csv(
         &self,
         Parameters(args): Parameters<SaveCsvArgs>,
     ) -> Result<CallToolResult, McpError> {
-        match tools::save_csv(&args.filename, &args.data, args.append.unwrap_or(false)) {
+        match tools::save_csv(&self.output, &args.filename, &args.data, &args.options.options()) {
             Ok(path) => Ok(CallToolResult::success(vec![Content::text(format!(
                 "✅ Successfully saved to {}",
                 path
             ))])),
-            Err(e) => Ok(CallToolResult::success(vec![Content::text(format!("Error: {}", e))])),
+            Err(e) => Ok(e.into()),
         }
     }
 
-    #[tool(description = "데이터를 JSON 파일로 저장합니다. 경로를 지정하지 않으면 바탕화면에 저장됩니다.")]
+    #[tool(description = "데이터 배열을 표 형식 파일로 저장합니다: csv, tsv, json, jsonl, xlsx(Excel), parquet, sqlite(테이블), markdown. 파일은 출력 폴더(기본값: 바탕화면의 BrowseHand) 안에 저장됩니다.")]
+    fn save_data(
+        &self,
+        Parameters(args): Parameters<SaveDataArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let options = SaveOptions {
+            table: args.table.clone(),
+            ..args.options.options()
+        };
+        match tools::save_data(&self.output, &args.filename, args.format, &args.data, &options) {
+            Ok(path) => Ok(CallToolResult::success(vec![Content::text(format!(
+                "✅ Successfully saved to {}",
+                path
+            ))])),
+            Err(e) => Ok(e.into()),
+        }
+    }
+
+    #[tool(description = "데이터를 JSON 파일로 저장합니다. mode로 기존 파일의 배열에 추가하거나 객체에 병합하거나 key 기준으로 upsert할 수 있으며, 임시 파일에 쓴 뒤 교체하므로 중간에 실패해도 기존 파일이 손상되지 않습니다. 파일은 출력 폴더(기본값: 바탕화면의 BrowseHand) 안에 저장됩니다.")]
     fn save_to_json(
         &self,
         Parameters(args): Parameters<SaveJsonArgs>,
     ) -> Result<CallToolResult, McpError> {
-        match tools::save_json(&args.filename, &args.data) {
+        let options = SaveOptions {
+            json_mode: args.mode.unwrap_or_default(),
+            key: args.key,
+            ..Default::default()
+        };
+        match tools::save_json(&self.output, &args.filename, &args.data, &options) {
             Ok(path) => Ok(CallToolResult::success(vec![Content::text(format!(
                 "✅ Successfully saved to {}",
                 path
             ))])),
-            Err(e) => Ok(CallToolResult::success(vec![Content::text(format!("Error: {}", e))])),
+            Err(e) => Ok(e.into()),
         }
     }
 
@@ -195,29 +689,62 @@ impl BrowseHand {
     async fn scroll_page(
         &self,
         Parameters(args): Parameters<ScrollArgs>,
+        ct: CancellationToken,
     ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
         let bridge = self.bridge.read().await;
+        let direction = args.direction.as_deref().unwrap_or("down");
         let amount = args.amount.unwrap_or(500);
+        let until_stable = args.until_stable.unwrap_or(false);
+        let settle_ms = args.settle_ms.unwrap_or(1000).clamp(100, MAX_STABLE_SCROLL_MS);
+        // Only `until_stable` repeats; keep the whole run under the cap.
+        let max_rounds = if until_stable {
+            args.max_rounds.unwrap_or(20).clamp(1, MAX_STABLE_SCROLL_MS / settle_ms)
+        } else {
+            0
+        };
         
-        match bridge
-            .send_and_wait("scroll_page", serde_json::json!({
-                "direction": args.direction,
-                "amount": amount
-            }))
-            .await
-        {
-            Ok(_) => {
-                let suffix = if args.direction == "down" || args.direction == "up" {
-                    format!(" by {}px", amount)
+        let request = protocol::ScrollPage {
+            direction: direction.to_string(),
+            amount,
+            selector: args.selector.clone(),
+            into_view: args.into_view.clone(),
+            until_stable,
+            settle_ms,
+            max_rounds,
+        };
+        match bridge.send_and_wait(&caller, request, &args.target).await {
+            Ok(reply) => {
+                let action = if let Some(into_view) = &args.into_view {
+                    format!("{} into view", into_view)
+                } else if until_stable {
+                    if reply.rounds < max_rounds {
+                        format!("until content stopped loading ({} rounds)", reply.rounds)
+                    } else {
+                        format!("{} rounds; content was still loading", reply.rounds)
+                    }
+                } else if direction == "down" || direction == "up" {
+                    format!("{} by {}px", direction, amount)
                 } else {
-                    String::new()
+                    direction.to_string()
                 };
-                Ok(CallToolResult::success(vec![Content::text(format!(
-                    "✅ Scrolled {}{}",
-                    args.direction, suffix
-                ))]))
+                let mut result = CallToolResult::success(vec![Content::text(format!(
+                    "✅ Scrolled {} (position {}px of {}px{})",
+                    action,
+                    reply.scroll_top,
+                    reply.scroll_height,
+                    if reply.at_end { ", end reached" } else { "" }
+                ))]);
+                result.structured_content = Some(serde_json::json!({
+                    "scrollTop": reply.scroll_top,
+                    "scrollHeight": reply.scroll_height,
+                    "clientHeight": reply.client_height,
+                    "atEnd": reply.at_end,
+                    "rounds": reply.rounds,
+                }));
+                Ok(challenge::annotate(&bridge, &caller, &args.target, result).await)
             }
-            Err(e) => Ok(CallToolResult::success(vec![Content::text(format!("Error: {}", e))])),
+            Err(e) => Ok(e.into()),
         }
     }
 
@@ -225,30 +752,132 @@ impl BrowseHand {
     async fn click_element(
         &self,
         Parameters(args): Parameters<ClickArgs>,
+        ct: CancellationToken,
     ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
         let bridge = self.bridge.read().await;
         
-        match bridge
-            .send_and_wait("click_element", serde_json::json!({
-                "selector": args.selector,
-                "waitAfter": args.wait_after.unwrap_or(1000)
-            }))
-            .await
-        {
-            Ok(response) => {
-                if response["success"].as_bool().unwrap_or(false) {
-                    Ok(CallToolResult::success(vec![Content::text(format!(
-                        "✅ Clicked element: {}",
-                        args.selector
-                    ))]))
-                } else {
-                    Ok(CallToolResult::success(vec![Content::text(format!(
-                        "❌ Failed to click: {}",
-                        response["error"].as_str().unwrap_or("unknown")
-                    ))]))
+        let request = protocol::ClickElement {
+            selector: args.selector.clone(),
+            wait_after: args.wait_after.unwrap_or(1000).max(0) as u64,
+        };
+        match bridge.send_and_wait(&caller, request, &args.target).await {
+            Ok(_) => {
+                let result = CallToolResult::success(vec![Content::text(format!(
+                    "✅ Clicked element: {}",
+                    args.selector
+                ))]);
+                Ok(challenge::annotate(&bridge, &caller, &args.target, result).await)
+            }
+            Err(e) => Ok(e.into()),
+        }
+    }
+
+    #[tool(description = "폼 필드를 채웁니다. 셀렉터별로 필드 종류(text, select, checkbox, radio, date, file)와 값을 지정하면 실제 사용자 입력처럼 input/change 이벤트를 발생시키고, 필드별 성공 여부를 반환합니다.")]
+    async fn fill_form(
+        &self,
+        Parameters(args): Parameters<FillFormArgs>,
+        ct: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
+        let mut fields = Vec::with_capacity(args.fields.len());
+        for (selector, field) in &args.fields {
+            let kind = field.kind.unwrap_or_default();
+            let mut input = protocol::FormInput {
+                selector: selector.clone(),
+                kind,
+                value: field.value.clone(),
+                file: None,
+            };
+            if let FieldKind::File = kind {
+                let path = field.value.as_str().ok_or_else(|| {
+                    BrowseHandError::InvalidArgument(format!("file field {} needs a path string", selector))
+                });
+                match path.and_then(|path| tools::read_upload(&self.output, path)) {
+                    Ok(file) => input.file = Some(file),
+                    Err(e) => return Ok(e.into()),
                 }
             }
-            Err(e) => Ok(CallToolResult::success(vec![Content::text(format!("Error: {}", e))])),
+            fields.push(input);
+        }
+        
+        let request = protocol::FillForm {
+            fields,
+            submit_selector: args.submit_selector.clone(),
+        };
+        
+        let bridge = self.bridge.read().await;
+        
+        match bridge.send_and_wait(&caller, request, &args.target).await {
+            Ok(reply) => {
+                let results = &reply.results;
+                let total = results.len();
+                let filled = results.iter().filter(|r| r.success).count();
+                let status = if filled == total { "✅" } else { "⚠️" };
+                let mut result = CallToolResult::success(vec![Content::text(format!(
+                    "{} Filled {}/{} fields:\n{}",
+                    status,
+                    filled,
+                    total,
+                    serde_json::to_string_pretty(results).unwrap_or_default()
+                ))]);
+                result.structured_content = Some(serde_json::json!({ "results": results }));
+                Ok(result)
+            }
+            Err(e) => Ok(e.into()),
+        }
+    }
+
+    #[tool(description = "요소에 텍스트를 한 글자씩 입력합니다. keydown/keypress/input/keyup 이벤트를 발생시켜 React/Vue 입력창과 자동완성이 반응합니다.")]
+    async fn type_text(
+        &self,
+        Parameters(args): Parameters<TypeTextArgs>,
+        ct: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
+        let bridge = self.bridge.read().await;
+        
+        let request = protocol::TypeText {
+            text: args.text.clone(),
+            selector: args.selector.clone(),
+            delay: args.delay_ms.unwrap_or(0),
+            clear: args.clear.unwrap_or(false),
+        };
+        
+        match bridge.send_and_wait(&caller, request, &args.target).await {
+            Ok(_) => Ok(CallToolResult::success(vec![Content::text(format!(
+                "✅ Typed {} characters into {}",
+                args.text.chars().count(),
+                args.selector.as_deref().unwrap_or("focused element")
+            ))])),
+            Err(e) => Ok(e.into()),
+        }
+    }
+
+    #[tool(description = "키 또는 조합키를 누릅니다 (예: Enter, Tab, Escape, Control+A, Shift+Tab).")]
+    async fn press_keys(
+        &self,
+        Parameters(args): Parameters<PressKeysArgs>,
+        ct: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
+        if args.keys.is_empty() || args.keys.iter().any(|k| k.trim().is_empty()) {
+            return Ok(BrowseHandError::InvalidArgument("keys must be non-empty key names".into()).into());
+        }
+        
+        let bridge = self.bridge.read().await;
+        
+        let request = protocol::PressKeys {
+            keys: args.keys.clone(),
+            selector: args.selector.clone(),
+        };
+        
+        match bridge.send_and_wait(&caller, request, &args.target).await {
+            Ok(_) => Ok(CallToolResult::success(vec![Content::text(format!(
+                "✅ Pressed {}",
+                args.keys.join(", ")
+            ))])),
+            Err(e) => Ok(e.into()),
         }
     }
 
@@ -256,30 +885,69 @@ impl BrowseHand {
     async fn wait_for_element(
         &self,
         Parameters(args): Parameters<WaitArgs>,
+        ct: CancellationToken,
     ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
         let bridge = self.bridge.read().await;
         
-        match bridge
-            .send_and_wait("wait_for_element", serde_json::json!({
-                "selector": args.selector,
-                "timeout": args.timeout.unwrap_or(10000)
-            }))
-            .await
-        {
-            Ok(response) => {
-                if response["success"].as_bool().unwrap_or(false) {
-                    Ok(CallToolResult::success(vec![Content::text(format!(
-                        "✅ Element found: {}",
-                        args.selector
-                    ))]))
-                } else {
-                    Ok(CallToolResult::success(vec![Content::text(format!(
-                        "❌ Element not found: {}",
-                        args.selector
-                    ))]))
+        let request = protocol::WaitForElement {
+            selector: args.selector.clone(),
+            timeout: args.timeout.unwrap_or(10000).max(0) as u64,
+        };
+        match bridge.send_and_wait(&caller, request, &args.target).await {
+            Ok(_) => Ok(CallToolResult::success(vec![Content::text(format!(
+                "✅ Element found: {}",
+                args.selector
+            ))])),
+            Err(e) => Ok(e.into()),
+        }
+    }
+
+    #[tool(description = "캡차나 봇 차단 페이지를 사람이 해결할 때까지 대기합니다. 챌린지가 사라지면 반환하고, 시간 내에 해결되지 않으면 오류를 반환합니다.")]
+    async fn wait_for_human(
+        &self,
+        Parameters(args): Parameters<WaitForHumanArgs>,
+        ct: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
+        let timeout = std::time::Duration::from_secs(args.timeout_secs.unwrap_or(300));
+        let interval = std::time::Duration::from_millis(args.poll_interval_ms.unwrap_or(2000).max(250));
+        let started = std::time::Instant::now();
+        
+        loop {
+            // Re-acquire the bridge per poll so a long wait does not block
+            // extension reconnects
+            let detected = {
+                let bridge = self.bridge.read().await;
+                if !bridge.is_connected() {
+                    return Ok(BrowseHandError::NotConnected.into());
+                }
+                challenge::detect(&bridge, &caller, &args.target).await
+            };
+            
+            let unresolved = match detected {
+                Ok(None) => {
+                    let mut result = CallToolResult::success(vec![Content::text(format!(
+                        "✅ No challenge present (waited {}s)",
+                        started.elapsed().as_secs()
+                    ))]);
+                    result.structured_content = Some(serde_json::json!({ "status": "clear" }));
+                    return Ok(result);
                 }
+                Ok(Some(challenge)) => BrowseHandError::ChallengeUnresolved(challenge.kind),
+                // The page may be reloading after the human solved the
+                // challenge; check again on the next poll.
+                Err(e) if e.is_transient() => e,
+                Err(e) => return Ok(e.into()),
+            };
+            
+            if started.elapsed() >= timeout {
+                return Ok(unresolved.into());
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = caller.cancel.cancelled() => return Ok(BrowseHandError::Cancelled.into()),
             }
-            Err(e) => Ok(CallToolResult::success(vec![Content::text(format!("Error: {}", e))])),
         }
     }
 
@@ -287,61 +955,139 @@ impl BrowseHand {
     async fn extract_structured_data(
         &self,
         Parameters(args): Parameters<ExtractArgs>,
+        ct: CancellationToken,
     ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
         let bridge = self.bridge.read().await;
         
-        let mut payload = serde_json::json!({
-            "containerSelector": args.container_selector,
-            "fields": args.fields
-        });
-        if let Some(l) = args.limit {
-            payload["limit"] = serde_json::json!(l);
-        }
-        
-        match bridge.send_and_wait("extract_structured_data", payload).await {
-            Ok(response) => {
-                if response["success"].as_bool().unwrap_or(false) {
-                    let data = &response["data"];
-                    let count = data.as_array().map(|a| a.len()).unwrap_or(0);
-                    Ok(CallToolResult::success(vec![Content::text(format!(
-                        "✅ Extracted {} items:\n{}",
-                        count,
-                        serde_json::to_string_pretty(data).unwrap_or_default()
-                    ))]))
-                } else {
-                    Ok(CallToolResult::success(vec![Content::text(format!(
-                        "❌ Extraction failed: {}",
-                        response["error"].as_str().unwrap_or("unknown")
-                    ))]))
-                }
+        match bridge.send_and_wait(&caller, args.request(), &args.target).await {
+            Ok(reply) => {
+                let mut result = CallToolResult::success(vec![Content::text(format!(
+                    "✅ Extracted {} items:\n{}",
+                    reply.data.len(),
+                    serde_json::to_string_pretty(&reply.data).unwrap_or_default()
+                ))]);
+                result.structured_content = Some(serde_json::json!({ "data": reply.data }));
+                Ok(result)
             }
-            Err(e) => Ok(CallToolResult::success(vec![Content::text(format!("Error: {}", e))])),
+            Err(e) => Ok(e.into()),
+        }
+    }
+
+    #[tool(description = "페이지네이션이나 무한 스크롤을 따라가며 여러 페이지의 구조화된 데이터를 한 번에 추출합니다. next_selector를 주면 다음 버튼을 클릭하고, 없으면 scroll_container(또는 페이지 전체)를 끝까지 스크롤합니다. dedupe_key로 중복을 제거하고, save_to를 주면 CSV로 바로 저장합니다.")]
+    async fn extract_all_pages(
+        &self,
+        Parameters(args): Parameters<ExtractAllArgs>,
+        ct: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
+        let crawl = match paging::crawl(&self.bridge, &caller, &args, &self.output).await {
+            Ok(crawl) => crawl,
+            Err(e) => return Ok(e.into()),
+        };
+        
+        let mut text = format!(
+            "✅ Extracted {} unique items from {} pages (stopped: {})",
+            crawl.rows.len(),
+            crawl.pages,
+            crawl.stop_reason
+        );
+        if let Some(error) = &crawl.error {
+            text.push_str(&format!("\n⚠️ {}", error));
+        }
+        if let Some(path) = &crawl.saved_to {
+            text.push_str(&format!("\n💾 Saved to {}", path));
+        }
+        if let Some(challenge) = &crawl.challenge {
+            text.push_str(&format!(
+                "\n⚠️ {} challenge detected ({}). A human must solve it in the browser; call wait_for_human to continue once it is cleared.",
+                challenge.kind, challenge.detail
+            ));
         }
+        text.push_str(&format!("\n{}", serde_json::to_string_pretty(&crawl.rows).unwrap_or_default()));
+        
+        let mut result = CallToolResult::success(vec![Content::text(text)]);
+        result.structured_content = Some(crawl.to_json());
+        Ok(result)
     }
 
-    #[tool(description = "현재 브라우저 탭의 URL을 가져옵니다.")]
-    async fn get_current_url(&self) -> Result<CallToolResult, McpError> {
+    #[tool(description = "브라우저 탭의 URL을 가져옵니다. tab_id를 지정하지 않으면 현재 활성 탭을 사용합니다.")]
+    async fn get_current_url(
+        &self,
+        Parameters(target): Parameters<Target>,
+        ct: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
         let bridge = self.bridge.read().await;
         
-        match bridge.send_and_wait("get_current_url", serde_json::json!({})).await {
-            Ok(response) => Ok(CallToolResult::success(vec![Content::text(format!(
+        match bridge.send_and_wait(&caller, protocol::GetCurrentUrl {}, &target).await {
+            Ok(reply) => Ok(CallToolResult::success(vec![Content::text(format!(
                 "Current URL: {}",
-                response["url"].as_str().unwrap_or("unknown")
+                reply.url.as_deref().unwrap_or("unknown")
             ))])),
-            Err(e) => Ok(CallToolResult::success(vec![Content::text(format!("Error: {}", e))])),
+            Err(e) => Ok(e.into()),
+        }
+    }
+
+    #[tool(description = "브라우저 탭의 스크린샷을 찍어 이미지로 반환합니다. 보이는 영역, 전체 페이지, 특정 요소를 캡처할 수 있고 파일로 저장할 수도 있습니다. 백그라운드 탭은 캡처하는 동안 잠시 활성 탭으로 전환되었다가 원래 탭으로 돌아옵니다.")]
+    async fn take_screenshot(
+        &self,
+        Parameters(args): Parameters<ScreenshotArgs>,
+        ct: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
+        let mode = args.mode.unwrap_or_default();
+        let format = args.format.unwrap_or_default();
+        
+        if mode == ScreenshotMode::Element && args.selector.is_none() {
+            return Ok(BrowseHandError::InvalidArgument("selector is required for element mode".into()).into());
+        }
+        
+        let request = protocol::TakeScreenshot {
+            mode,
+            format,
+            quality: args.quality.unwrap_or(80).min(100),
+            selector: args.selector.clone(),
+            max_width: args.max_width,
+        };
+        
+        let bridge = self.bridge.read().await;
+        
+        let reply = match bridge.send_and_wait(&caller, request, &args.target).await {
+            Ok(reply) => reply,
+            Err(e) => return Ok(e.into()),
+        };
+        
+        let mut summary = format!("✅ Captured {}x{} screenshot", reply.width, reply.height);
+        
+        if let Some(filename) = &args.save_to {
+            match tools::save_image(&self.output, filename, &reply.data) {
+                Ok(path) => summary.push_str(&format!(", saved to {}", path)),
+                Err(e) => return Ok(e.into()),
+            }
         }
+        
+        Ok(CallToolResult::success(vec![
+            Content::image(reply.data, reply.mime_type),
+            Content::text(summary),
+        ]))
     }
 
     #[tool(description = "AI 분석을 위해 현재 페이지의 DOM 구조(주요 태그와 텍스트)를 가져옵니다. 불필요한 태그는 제거됩니다.")]
-    async fn get_dom_snapshot(&self) -> Result<CallToolResult, McpError> {
+    async fn get_dom_snapshot(
+        &self,
+        Parameters(target): Parameters<Target>,
+        ct: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
         let bridge = self.bridge.read().await;
         
-        match bridge.send_and_wait("get_dom_snapshot", serde_json::json!({})).await {
-            Ok(response) => Ok(CallToolResult::success(vec![Content::text(format!(
+        match bridge.send_and_wait(&caller, protocol::GetDomSnapshot {}, &target).await {
+            Ok(reply) => Ok(CallToolResult::success(vec![Content::text(format!(
                 "DOM Snapshot:\n{}",
-                response["html"].as_str().unwrap_or("")
+                reply.html
             ))])),
-            Err(e) => Ok(CallToolResult::success(vec![Content::text(format!("Error: {}", e))])),
+            Err(e) => Ok(e.into()),
         }
     }
 
@@ -349,27 +1095,177 @@ impl BrowseHand {
     async fn navigate_to(
         &self,
         Parameters(args): Parameters<NavigateArgs>,
+        ct: CancellationToken,
     ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
         let bridge = self.bridge.read().await;
         
         match bridge
-            .send_and_wait("navigate_to", serde_json::json!({ "url": args.url }))
+            .send_and_wait(&caller, protocol::NavigateTo { url: args.url.clone() }, &args.target)
             .await
         {
-            Ok(response) => {
-                if response["success"].as_bool().unwrap_or(false) {
-                    Ok(CallToolResult::success(vec![Content::text(format!(
-                        "✅ Navigated to: {}",
-                        args.url
-                    ))]))
-                } else {
-                    Ok(CallToolResult::success(vec![Content::text(format!(
-                        "❌ Navigation failed: {}",
-                        response["error"].as_str().unwrap_or("unknown")
-                    ))]))
-                }
+            Ok(_) => {
+                let result = CallToolResult::success(vec![Content::text(format!(
+                    "✅ Navigated to: {}",
+                    args.url
+                ))]);
+                Ok(challenge::annotate(&bridge, &caller, &args.target, result).await)
             }
-            Err(e) => Ok(CallToolResult::success(vec![Content::text(format!("Error: {}", e))])),
+            Err(e) => Ok(e.into()),
+        }
+    }
+
+    #[tool(description = "열려 있는 브라우저 탭 목록(탭 ID, 창 ID, 제목, URL, 활성 여부)을 가져옵니다.")]
+    async fn list_tabs(
+        &self,
+        Parameters(args): Parameters<ListTabsArgs>,
+        ct: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
+        let bridge = self.bridge.read().await;
+        
+        let request = protocol::ListTabs { window_id: args.window_id };
+        
+        match bridge
+            .send_and_wait(&caller, request, &Target { tab_id: None, browser: args.browser }).await {
+            Ok(reply) => Ok(CallToolResult::success(vec![Content::text(format!(
+                "Open tabs ({}):\n{}",
+                reply.tabs.len(),
+                serde_json::to_string_pretty(&reply.tabs).unwrap_or_default()
+            ))])),
+            Err(e) => Ok(e.into()),
+        }
+    }
+
+    #[tool(description = "새 브라우저 탭을 엽니다. 생성된 탭 ID를 반환합니다.")]
+    async fn open_tab(
+        &self,
+        Parameters(args): Parameters<OpenTabArgs>,
+        ct: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
+        let bridge = self.bridge.read().await;
+        
+        let request = protocol::OpenTab {
+            url: args.url,
+            active: args.active.unwrap_or(true),
+            window_id: args.window_id,
+        };
+        
+        match bridge
+            .send_and_wait(&caller, request, &Target { tab_id: None, browser: args.browser }).await {
+            Ok(reply) => Ok(CallToolResult::success(vec![Content::text(format!(
+                "✅ Opened tab {}:\n{}",
+                reply.tab.id,
+                serde_json::to_string_pretty(&reply.tab).unwrap_or_default()
+            ))])),
+            Err(e) => Ok(e.into()),
+        }
+    }
+
+    #[tool(description = "브라우저 탭을 닫습니다.")]
+    async fn close_tab(
+        &self,
+        Parameters(args): Parameters<TabIdArgs>,
+        ct: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
+        let bridge = self.bridge.read().await;
+        let target = Target {
+            tab_id: Some(args.tab_id),
+            browser: args.browser,
+        };
+        
+        match bridge.send_and_wait(&caller, protocol::CloseTab {}, &target).await {
+            Ok(_) => Ok(CallToolResult::success(vec![Content::text(format!(
+                "✅ Closed tab {}",
+                args.tab_id
+            ))])),
+            Err(e) => Ok(e.into()),
+        }
+    }
+
+    #[tool(description = "브라우저 탭을 활성화하고 해당 창에 포커스를 줍니다.")]
+    async fn activate_tab(
+        &self,
+        Parameters(args): Parameters<TabIdArgs>,
+        ct: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let caller = self.caller(ct);
+        let bridge = self.bridge.read().await;
+        let target = Target {
+            tab_id: Some(args.tab_id),
+            browser: args.browser,
+        };
+        
+        match bridge.send_and_wait(&caller, protocol::ActivateTab {}, &target).await {
+            Ok(_) => Ok(CallToolResult::success(vec![Content::text(format!(
+                "✅ Activated tab {}",
+                args.tab_id
+            ))])),
+            Err(e) => Ok(e.into()),
+        }
+    }
+
+    #[tool(description = "도구 호출 순서(예: navigate_to → wait_for_element → extract_structured_data → save_to_csv)를 cron 일정으로 등록합니다. 작업은 로컬에 저장되며 Extension이 연결되어 있을 때 서버가 자동으로 실행합니다.")]
+    async fn schedule_job(
+        &self,
+        Parameters(args): Parameters<ScheduleJobArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        match self.jobs.add(args.name, args.cron, args.steps).await {
+            Ok(job) => Ok(CallToolResult::success(vec![Content::text(format!(
+                "✅ Scheduled job '{}' (id: {}), next run: {}",
+                job.name,
+                job.id,
+                job.next_run().map(|t| t.to_rfc3339()).unwrap_or_else(|| "never".into())
+            ))])),
+            Err(e) => Ok(e.into()),
+        }
+    }
+
+    #[tool(description = "등록된 예약 작업 목록(일정, 단계, 다음/마지막 실행, 마지막 결과)을 가져옵니다.")]
+    async fn list_jobs(&self) -> Result<CallToolResult, McpError> {
+        let jobs: Vec<Value> = self.jobs.list().await.iter().map(|j| j.to_json()).collect();
+        
+        if jobs.is_empty() {
+            return Ok(CallToolResult::success(vec![Content::text("No scheduled jobs.")]));
+        }
+        
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Scheduled jobs ({}):\n{}",
+            jobs.len(),
+            serde_json::to_string_pretty(&jobs).unwrap_or_default()
+        ))]))
+    }
+
+    #[tool(description = "예약 작업을 일정과 관계없이 지금 바로 실행하고 결과를 반환합니다.")]
+    async fn run_job_now(
+        &self,
+        Parameters(args): Parameters<JobIdArgs>,
+        ct: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(job) = self.jobs.find(&args.job).await else {
+            return Ok(BrowseHandError::InvalidArgument(format!("job not found: {}", args.job)).into());
+        };
+        if !self.bridge.read().await.is_connected() {
+            return Ok(BrowseHandError::NotConnected.into());
+        }
+        
+        Ok(self.run_job(&job, ct).await.into_result(&job))
+    }
+
+    #[tool(description = "예약 작업을 삭제합니다.")]
+    async fn delete_job(
+        &self,
+        Parameters(args): Parameters<JobIdArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        match self.jobs.remove(&args.job).await {
+            Ok(Some(job)) => Ok(CallToolResult::success(vec![Content::text(format!(
+                "✅ Deleted job '{}' (id: {})",
+                job.name, job.id
+            ))])),
+            Ok(None) => Ok(BrowseHandError::InvalidArgument(format!("job not found: {}", args.job)).into()),
+            Err(e) => Ok(e.into()),
         }
     }
 }
@@ -381,38 +1277,99 @@ impl ServerHandler for BrowseHand {
             protocol_version: ProtocolVersion::V_2024_11_05,
             capabilities: ServerCapabilities::builder()
                 .enable_tools()
+                .enable_resources()
                 .build(),
             server_info: Implementation::from_build_env(),
             instructions: Some("AI가 브라우저를 직접 제어하는 로컬 에이전트입니다. Chrome Extension이 연결되어 있어야 브라우저 제어 도구를 사용할 수 있습니다.".into()),
         }
     }
+
+    async fn list_resources(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        let mut events = RawResource::new(EVENTS_URI, "events");
+        events.description = Some("브라우저 이벤트 기록 (탭 이동, 페이지 로드, 콘솔 오류, 대화상자, 다운로드)".into());
+        events.mime_type = Some("application/json".into());
+        Ok(ListResourcesResult::with_all_items(vec![events.no_annotation()]))
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        if request.uri != EVENTS_URI {
+            return Err(McpError::resource_not_found(format!("unknown resource: {}", request.uri), None));
+        }
+        let bridge = self.bridge.read().await;
+        let page = bridge.events().query(&EventFilter {
+            limit: bridge.events().capacity(),
+            ..Default::default()
+        });
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::TextResourceContents {
+                uri: EVENTS_URI.to_string(),
+                mime_type: Some("application/json".into()),
+                text: serde_json::to_string_pretty(&page.to_json()).unwrap_or_default(),
+                meta: None,
+            }],
+        })
+    }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter("browsehand=info")
-        .with_writer(std::io::stderr)
-        .with_ansi(false)
-        .init();
+/// Serves one MCP session per authenticated control socket (the CLI's
+/// `status` and `call`), alongside the stdio session.
+async fn serve_control(agent: BrowseHand, mut control: mpsc::UnboundedReceiver<DuplexStream>) {
+    while let Some(io) = control.recv().await {
+        let agent = agent.for_client("control");
+        tokio::spawn(async move {
+            match agent.serve(io).await {
+                Ok(service) => {
+                    let _ = service.waiting().await;
+                }
+                Err(e) => error!("control session error: {:?}", e),
+            }
+        });
+    }
+}
 
+async fn serve(config: Config) -> Result<()> {
     info!("Starting BrowseHand MCP Server...");
 
-    let bridge = Arc::new(RwLock::new(ExtensionBridge::new()));
-    let bridge_clone = Arc::clone(&bridge);
+    let pairing = Arc::new(auth::Pairing::load_or_create(config.extension_ids.clone())?);
+    let tls = if config.tls { Some(tls::acceptor(&config)?) } else { None };
+    let http_listener = if config.http { Some(http::bind(config.http_addr()).await?) } else { None };
+    let bridge = Arc::new(RwLock::new(ExtensionBridge::new(&config)));
+    let (control_tx, control_rx) = mpsc::unbounded_channel();
 
-    tokio::spawn(async move {
-        if let Err(e) = websocket::run_websocket_server(bridge_clone).await {
-            error!("WebSocket server error: {}", e);
+    match websocket::bind(&config).await {
+        Ok(listener) => {
+            let bridge_clone = Arc::clone(&bridge);
+            let pairing = Arc::clone(&pairing);
+            tokio::spawn(async move {
+                if let Err(e) = websocket::run_websocket_server(listener, tls, bridge_clone, pairing, control_tx).await {
+                    error!("WebSocket server error: {}", e);
+                }
+            });
         }
-    });
+        Err(e) if config.fail_fast => return Err(e),
+        Err(e) => error!("{:#}; continuing without a browser bridge", e),
+    }
 
-    info!("WebSocket server listening on ws://localhost:8765");
+    let jobs = Arc::new(JobStore::load()?);
+    let agent = BrowseHand::new(bridge, jobs, Arc::new(OutputDir::new(&config)));
 
-    let agent = BrowseHand::new(bridge);
+    tokio::spawn(jobs::run_scheduler(agent.for_client("scheduler")));
+    tokio::spawn(serve_control(agent.clone(), control_rx));
 
     info!("MCP Server ready. Waiting for Chrome Extension connection...");
 
+    if let Some(listener) = http_listener {
+        return http::serve(listener, agent, pairing).await;
+    }
+
     let service = agent.serve(stdio()).await.inspect_err(|e| {
         error!("serving error: {:?}", e);
     })?;
@@ -421,3 +1378,28 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::main]
+async fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve);
+
+    // One-shot commands print their own output; keep the log to warnings.
+    let filter = if matches!(command, Command::Serve) { "browsehand=info" } else { "browsehand=warn" };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(false)
+        .init();
+
+    match command {
+        Command::Serve => serve(Config::load(&cli.config)?).await.map(|_| ExitCode::SUCCESS),
+        Command::Status => cli::status(&Config::load(&cli.config)?).await,
+        Command::Call { tool, args, json } => cli::call(&Config::load(&cli.config)?, &tool, &args, json).await,
+        Command::Config(ConfigCommand::Init { force }) => cli::config_init(&cli.config, force),
+        Command::Schema => {
+            println!("{}", serde_json::to_string_pretty(&protocol::schema())?);
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
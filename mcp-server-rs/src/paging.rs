@@ -0,0 +1,199 @@
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use crate::challenge::{self, Challenge};
+use crate::error::{BrowseHandError, Result};
+use crate::export::SaveOptions;
+use crate::protocol::{ClickElement, ExtractStructuredData, ScrollPage};
+use crate::tools::{self, OutputDir};
+use crate::websocket::{Caller, ExtensionBridge, Target};
+use crate::ExtractAllArgs;
+
+const DEFAULT_MAX_ITERATIONS: u32 = 20;
+const DEFAULT_WAIT_MS: u64 = 1500;
+
+/// What a multi-page extraction collected and why it stopped.
+pub struct Crawl {
+    pub rows: Vec<Value>,
+    pub pages: u32,
+    pub stop_reason: &'static str,
+    pub saved_to: Option<String>,
+    pub error: Option<String>,
+    pub challenge: Option<Challenge>,
+}
+
+impl Crawl {
+    pub fn to_json(&self) -> Value {
+        let mut json = serde_json::json!({
+            "data": self.rows,
+            "pages": self.pages,
+            "stopReason": self.stop_reason,
+            "savedTo": self.saved_to,
+        });
+        if let Some(error) = &self.error {
+            json["error"] = serde_json::json!(error);
+        }
+        if let Some(challenge) = &self.challenge {
+            json["status"] = serde_json::json!("human_required");
+            json["challenge"] = challenge.to_json();
+        }
+        json
+    }
+}
+
+/// Identity of a row for de-duplication: the `key` field when present,
+/// otherwise the whole row.
+fn row_key(row: &Value, key: Option<&str>) -> String {
+    match key.map(|k| &row[k]) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => row.to_string(),
+        Some(other) => other.to_string(),
+    }
+}
+
+async fn extract_page(
+    bridge: &ExtensionBridge,
+    caller: &Caller,
+    request: &ExtractStructuredData,
+    target: &Target,
+) -> Result<Vec<Value>> {
+    let reply = bridge.send_and_wait(caller, request.clone(), target).await?;
+    Ok(reply.data)
+}
+
+/// Moves to the next batch of items, either by clicking the "next" control or
+/// by scrolling the container (or the page) to the bottom. Returns false when
+/// there is no next control left.
+async fn advance(bridge: &ExtensionBridge, caller: &Caller, args: &ExtractAllArgs) -> Result<bool> {
+    let target = &args.extract.target;
+    let Some(selector) = &args.next_selector else {
+        let scroll = ScrollPage {
+            direction: "bottom".to_string(),
+            amount: 0,
+            selector: args.scroll_container.clone(),
+            into_view: None,
+            until_stable: false,
+            settle_ms: 0,
+            max_rounds: 0,
+        };
+        return bridge.send_and_wait(caller, scroll, target).await.map(|_| true);
+    };
+    let click = ClickElement {
+        selector: selector.clone(),
+        wait_after: 0,
+    };
+    match bridge.send_and_wait(caller, click, target).await {
+        Ok(_) => Ok(true),
+        Err(BrowseHandError::SelectorNotFound(_)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Repeats extract → advance until the target count is reached, a page adds
+/// nothing new, the next control disappears or the iteration bound is hit.
+/// New rows are appended to `save_to` page by page, so a crawl that fails
+/// halfway still leaves what it found on disk.
+///
+/// The bridge lock is taken per request rather than for the whole crawl so a
+/// long run does not block browsers from connecting.
+pub async fn crawl(
+    bridge: &Arc<RwLock<ExtensionBridge>>,
+    caller: &Caller,
+    args: &ExtractAllArgs,
+    output: &OutputDir,
+) -> Result<Crawl> {
+    let target = &args.extract.target;
+    let request = args.extract.request();
+    let max_iterations = args.max_iterations.unwrap_or(DEFAULT_MAX_ITERATIONS).max(1);
+    let wait = Duration::from_millis(args.wait_ms.unwrap_or(DEFAULT_WAIT_MS));
+    let mut append = args.append.unwrap_or(false);
+
+    let mut crawl = Crawl {
+        rows: Vec::new(),
+        pages: 0,
+        stop_reason: "max_iterations",
+        saved_to: None,
+        error: None,
+        challenge: None,
+    };
+    let mut seen = HashSet::new();
+
+    for iteration in 0..max_iterations {
+        let page = match extract_page(&*bridge.read().await, caller, &request, target).await {
+            Ok(page) => page,
+            Err(e) if crawl.rows.is_empty() => return Err(e),
+            Err(e) => {
+                warn!("extract_all_pages stopped on page {}: {}", iteration + 1, e);
+                crawl.stop_reason = "error";
+                crawl.error = Some(e.to_string());
+                break;
+            }
+        };
+        crawl.pages += 1;
+
+        let mut fresh: Vec<Value> = page
+            .into_iter()
+            .filter(|row| seen.insert(row_key(row, args.dedupe_key.as_deref())))
+            .collect();
+        if let Some(count) = args.target_count {
+            fresh.truncate(count.saturating_sub(crawl.rows.len()));
+        }
+
+        if let (Some(filename), false) = (&args.save_to, fresh.is_empty()) {
+            crawl.saved_to = Some(tools::save_csv(
+                output,
+                filename,
+                &Value::Array(fresh.clone()),
+                &SaveOptions { append, ..Default::default() },
+            )?);
+            append = true;
+        }
+
+        let added = fresh.len();
+        crawl.rows.extend(fresh);
+
+        if args.target_count.is_some_and(|count| crawl.rows.len() >= count) {
+            crawl.stop_reason = "target_count";
+            break;
+        }
+        if added == 0 {
+            crawl.stop_reason = "no_new_items";
+            break;
+        }
+        if iteration + 1 == max_iterations {
+            break;
+        }
+
+        match advance(&*bridge.read().await, caller, args).await {
+            Ok(true) => {}
+            Ok(false) => {
+                crawl.stop_reason = "no_next_page";
+                break;
+            }
+            Err(e) => {
+                warn!("extract_all_pages could not advance past page {}: {}", iteration + 1, e);
+                crawl.stop_reason = "error";
+                crawl.error = Some(e.to_string());
+                break;
+            }
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = caller.cancel.cancelled() => return Err(BrowseHandError::Cancelled),
+        }
+
+        // A page that cannot be checked is left to the next extraction to fail.
+        if let Ok(Some(found)) = challenge::detect(&*bridge.read().await, caller, target).await {
+            crawl.stop_reason = "challenge";
+            crawl.challenge = Some(found);
+            break;
+        }
+    }
+
+    Ok(crawl)
+}
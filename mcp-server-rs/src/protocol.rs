@@ -0,0 +1,562 @@
+//! Messages exchanged with the browser extension over the bridge socket.
+//!
+//! The server sends a request as `{type, requestId, tabId?, payload}`. The
+//! extension answers with the same `type` and `requestId`, plus either
+//! `success: true` and the reply's fields, or `success: false` and a
+//! [`Failure`]. Besides answers, the extension pushes `event` messages about
+//! what happens in the browser. `browsehand schema` prints the JSON Schema of
+//! all of it.
+
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::BrowseHandError;
+use crate::{FieldKind, ImageFormat, ScreenshotMode};
+
+/// Bumped on every incompatible change to the messages below. Both ends send
+/// it in their hello, and the server turns away extensions on another version.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// A request with an answer of type `Reply`.
+pub trait Call: Into<Request> {
+    type Reply;
+
+    /// Picks this request's answer out of a parsed reply, or None if the
+    /// extension answered with another type.
+    fn reply(reply: Reply) -> Option<Self::Reply>;
+}
+
+/// Lists every request once: `type` on the wire, payload struct, reply struct.
+/// Generates the `Request` and `Reply` enums and the `Call` impls from it.
+macro_rules! bridge_requests {
+    ($($kind:ident: $request:ident => $reply:ident,)*) => {
+        #[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+        #[serde(tag = "type", content = "payload", rename_all = "snake_case")]
+        pub enum Request {
+            $($request($request),)*
+        }
+
+        #[derive(Debug, Deserialize, schemars::JsonSchema)]
+        #[serde(tag = "type", rename_all = "snake_case")]
+        pub enum Reply {
+            $($request($reply),)*
+        }
+
+        impl Request {
+            /// The request's `type` on the wire.
+            pub fn kind(&self) -> &'static str {
+                match self {
+                    $(Request::$request(_) => stringify!($kind),)*
+                }
+            }
+        }
+
+        $(
+            impl From<$request> for Request {
+                fn from(request: $request) -> Self {
+                    Request::$request(request)
+                }
+            }
+
+            impl Call for $request {
+                type Reply = $reply;
+
+                fn reply(reply: Reply) -> Option<$reply> {
+                    match reply {
+                        Reply::$request(reply) => Some(reply),
+                        #[allow(unreachable_patterns)]
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+bridge_requests! {
+    ping: Ping => PingReply,
+    read_content: ReadContent => ContentReply,
+    execute_script: ExecuteScript => ScriptReply,
+    scroll_page: ScrollPage => ScrollReply,
+    click_element: ClickElement => Done,
+    fill_form: FillForm => FormReply,
+    type_text: TypeText => Done,
+    press_keys: PressKeys => Done,
+    wait_for_element: WaitForElement => Done,
+    extract_structured_data: ExtractStructuredData => ExtractReply,
+    get_current_url: GetCurrentUrl => UrlReply,
+    navigate_to: NavigateTo => Done,
+    detect_challenge: DetectChallenge => ChallengeReply,
+    take_screenshot: TakeScreenshot => ScreenshotReply,
+    get_dom_snapshot: GetDomSnapshot => SnapshotReply,
+    list_tabs: ListTabs => TabsReply,
+    open_tab: OpenTab => TabReply,
+    close_tab: CloseTab => Done,
+    activate_tab: ActivateTab => Done,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct Ping {}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct ReadContent {
+    pub selector: String,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct ExecuteScript {
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrollPage {
+    pub direction: String,
+    pub amount: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub into_view: Option<String>,
+    pub until_stable: bool,
+    pub settle_ms: u64,
+    pub max_rounds: u64,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ClickElement {
+    pub selector: String,
+    pub wait_after: u64,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FillForm {
+    pub fields: Vec<FormInput>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub submit_selector: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct FormInput {
+    pub selector: String,
+    pub kind: FieldKind,
+    pub value: Value,
+    /// `{name, mimeType, data}` of the file to upload, for `file` fields.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct TypeText {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+    /// Milliseconds between keystrokes.
+    pub delay: u32,
+    pub clear: bool,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct PressKeys {
+    pub keys: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct WaitForElement {
+    pub selector: String,
+    /// Milliseconds.
+    pub timeout: u64,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractStructuredData {
+    pub container_selector: String,
+    /// Field name → CSS selector within each container.
+    pub fields: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct GetCurrentUrl {}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct NavigateTo {
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct DetectChallenge {}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TakeScreenshot {
+    pub mode: ScreenshotMode,
+    pub format: ImageFormat,
+    pub quality: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct GetDomSnapshot {}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ListTabs {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenTab {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct CloseTab {}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct ActivateTab {}
+
+/// A browser tab as the extension describes it.
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TabInfo {
+    pub id: i64,
+    pub window_id: i64,
+    pub index: i64,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub active: bool,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PingReply {
+    pub version: Option<String>,
+    pub active_tab: Option<TabInfo>,
+}
+
+/// Success without data.
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct Done {}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ContentReply {
+    pub data: String,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ScriptReply {
+    /// The script's completion value; null when it was undefined.
+    #[serde(default)]
+    pub result: Value,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrollReply {
+    pub scroll_top: i64,
+    pub scroll_height: i64,
+    pub client_height: i64,
+    pub at_end: bool,
+    pub rounds: u64,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct FormReply {
+    pub results: Vec<FieldResult>,
+}
+
+/// Outcome of one form field, or of the submit click (`submit: true`).
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct FieldResult {
+    pub selector: String,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub submit: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExtractReply {
+    /// One object per container, keyed by field name.
+    pub data: Vec<Value>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct UrlReply {
+    /// Missing for pages the extension may not read the URL of.
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ChallengeReply {
+    pub detected: bool,
+    pub kind: Option<String>,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreenshotReply {
+    pub mime_type: String,
+    /// Base64-encoded image.
+    pub data: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SnapshotReply {
+    pub html: String,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct TabsReply {
+    pub tabs: Vec<TabInfo>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct TabReply {
+    pub tab: TabInfo,
+}
+
+/// The fields of a `success: false` reply.
+#[derive(Debug, Default, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Failure {
+    pub error: Option<String>,
+    /// One of the error codes in the README, e.g. `selector_not_found`.
+    pub code: Option<String>,
+    pub selector: Option<String>,
+    pub tab_id: Option<i64>,
+}
+
+impl Request {
+    /// Error for a failure without a recognised `code`.
+    fn fallback(&self) -> fn(String) -> BrowseHandError {
+        match self {
+            Request::NavigateTo(_) => BrowseHandError::NavigationFailed,
+            Request::ExecuteScript(_) => BrowseHandError::ScriptException,
+            _ => BrowseHandError::Extension,
+        }
+    }
+
+    /// Reads the extension's answer to this request.
+    pub fn parse_reply<C: Call>(&self, message: Value) -> Result<C::Reply, BrowseHandError> {
+        if message.get("success") == Some(&Value::Bool(false)) {
+            let failure = Failure::deserialize(&message).unwrap_or_default();
+            return Err(BrowseHandError::from_failure(failure, self.fallback()));
+        }
+        if message["type"] != self.kind() {
+            return Err(BrowseHandError::Protocol(format!(
+                "expected a {} reply, got {}",
+                self.kind(),
+                message["type"]
+            )));
+        }
+        let reply = Reply::deserialize(&message).map_err(|e| {
+            BrowseHandError::Protocol(format!("malformed {} reply: {}", self.kind(), e))
+        })?;
+        C::reply(reply).ok_or_else(|| BrowseHandError::Protocol(format!("unexpected {} reply", self.kind())))
+    }
+}
+
+/// A request as sent on the socket.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestMessage<'a> {
+    pub request_id: &'a str,
+    /// Tab to act on; the active tab of the focused window when missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tab_id: Option<i64>,
+    #[serde(flatten)]
+    pub request: &'a Request,
+}
+
+/// Messages the client sends on its own: the hello that opens a connection
+/// and, from extensions, events.
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Hello(ClientHello),
+    Event(BrowserEvent),
+}
+
+/// Something that happened in the browser, pushed by the extension as it
+/// happens.
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case", rename_all_fields = "camelCase")]
+pub enum BrowserEvent {
+    /// A tab started loading another URL.
+    Navigation { tab_id: i64, url: String },
+    /// A tab finished loading.
+    Load {
+        tab_id: i64,
+        url: Option<String>,
+        title: Option<String>,
+    },
+    /// `console.error`, an uncaught exception (`source: "exception"`) or an
+    /// unhandled promise rejection (`source: "rejection"`) in a page.
+    ConsoleError {
+        tab_id: i64,
+        url: Option<String>,
+        message: String,
+        source: Option<String>,
+    },
+    /// A page opened an alert, confirm or prompt dialog.
+    Dialog {
+        tab_id: i64,
+        url: Option<String>,
+        dialog_type: String,
+        message: String,
+    },
+    /// A download started, finished or was interrupted.
+    Download {
+        download_id: i64,
+        url: Option<String>,
+        filename: Option<String>,
+        /// `in_progress`, `complete` or `interrupted`.
+        state: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Navigation,
+    Load,
+    ConsoleError,
+    Dialog,
+    Download,
+}
+
+impl BrowserEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            BrowserEvent::Navigation { .. } => EventKind::Navigation,
+            BrowserEvent::Load { .. } => EventKind::Load,
+            BrowserEvent::ConsoleError { .. } => EventKind::ConsoleError,
+            BrowserEvent::Dialog { .. } => EventKind::Dialog,
+            BrowserEvent::Download { .. } => EventKind::Download,
+        }
+    }
+
+    /// The tab the event happened in; downloads belong to no tab.
+    pub fn tab_id(&self) -> Option<i64> {
+        match self {
+            BrowserEvent::Navigation { tab_id, .. }
+            | BrowserEvent::Load { tab_id, .. }
+            | BrowserEvent::ConsoleError { tab_id, .. }
+            | BrowserEvent::Dialog { tab_id, .. } => Some(*tab_id),
+            BrowserEvent::Download { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ClientHello {
+    /// Browser session name; `default` when missing.
+    pub session: Option<String>,
+    pub version: Option<String>,
+    pub token: Option<String>,
+    /// `control` for CLI clients, which speak MCP over the socket.
+    pub role: Option<String>,
+    /// `PROTOCOL_VERSION` of the client; clients from before versioning
+    /// send none.
+    pub protocol: Option<u32>,
+}
+
+/// Messages the server sends on its own rather than as requests.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case", rename_all_fields = "camelCase")]
+pub enum ServerMessage {
+    Hello {
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        session: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        role: Option<String>,
+        protocol: u32,
+    },
+    AuthError {
+        message: String,
+    },
+    /// The client speaks another protocol version; the socket is closed after.
+    ProtocolError {
+        message: String,
+        protocol: u32,
+    },
+    /// Stop working on a request the server no longer waits for.
+    Cancel {
+        request_id: String,
+    },
+}
+
+impl ServerMessage {
+    pub fn to_text(&self) -> String {
+        serde_json::to_string(self).expect("server messages serialize")
+    }
+}
+
+// The shapes below exist only to describe the wire format in the schema.
+
+#[derive(schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+struct ReplyMessage {
+    request_id: String,
+    success: bool,
+    #[serde(flatten)]
+    reply: Reply,
+}
+
+#[derive(schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+struct FailureMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    request_id: String,
+    /// Always false.
+    success: bool,
+    #[serde(flatten)]
+    failure: Failure,
+}
+
+/// Any message on the bridge socket, in either direction.
+#[derive(schemars::JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum BridgeMessage<'a> {
+    Client(ClientMessage),
+    Server(ServerMessage),
+    Request(RequestMessage<'a>),
+    Reply(ReplyMessage),
+    Failure(FailureMessage),
+}
+
+/// JSON Schema of every message on the bridge socket.
+pub fn schema() -> Value {
+    let mut schema = serde_json::to_value(schemars::schema_for!(BridgeMessage)).expect("schema serializes");
+    schema["title"] = serde_json::json!(format!("BrowseHand bridge protocol v{}", PROTOCOL_VERSION));
+    schema
+}
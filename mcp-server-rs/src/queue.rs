@@ -0,0 +1,192 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::BrowseHandError;
+
+/// Requests that do not touch a page and so never wait behind page work.
+const UNQUEUED: &[&str] = &["ping", "list_tabs", "open_tab"];
+
+/// Whether requests of `msg_type` wait in a tab's lane.
+pub fn is_queued(msg_type: &str) -> bool {
+    !UNQUEUED.contains(&msg_type)
+}
+
+/// Requests for one tab (or for "whatever tab is active" when no id is
+/// given and no tab is active) of one browser. They run one at a time, in
+/// arrival order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct LaneKey {
+    browser: String,
+    tab: Option<i64>,
+}
+
+struct Running {
+    msg_type: String,
+    client: String,
+    since: Instant,
+}
+
+struct Lane {
+    turn: Arc<Semaphore>,
+    /// Requests waiting for or holding this lane.
+    depth: usize,
+    running: Option<Running>,
+}
+
+/// Per-tab FIFO lanes plus a per-browser cap on requests in flight. Tokio's
+/// semaphores are fair, so requests for a tab reach the extension in the order
+/// they were issued, whichever MCP client issued them.
+pub struct RequestQueues {
+    lanes: Mutex<HashMap<LaneKey, Lane>>,
+    browsers: Mutex<HashMap<String, Arc<Semaphore>>>,
+    max_concurrency: usize,
+    max_depth: usize,
+}
+
+/// A request's turn in its lane and a browser slot; both are given back when
+/// it is dropped.
+pub struct Turn<'a> {
+    queues: &'a RequestQueues,
+    key: Option<LaneKey>,
+    permits: Vec<OwnedSemaphorePermit>,
+}
+
+impl RequestQueues {
+    pub fn new(max_concurrency: usize, max_depth: usize) -> Self {
+        Self {
+            lanes: Mutex::new(HashMap::new()),
+            browsers: Mutex::new(HashMap::new()),
+            max_concurrency,
+            max_depth,
+        }
+    }
+
+    /// Waits until the request may be sent to the extension. Fails fast with
+    /// `QueueFull` when the tab already has `max_depth` requests lined up, so
+    /// a slow extension pushes back on callers instead of piling up work.
+    pub async fn enter(
+        &self,
+        browser: &str,
+        tab: Option<i64>,
+        msg_type: &str,
+        client: &str,
+        cancel: &CancellationToken,
+    ) -> Result<Turn<'_>, BrowseHandError> {
+        if !is_queued(msg_type) {
+            return Ok(Turn { queues: self, key: None, permits: Vec::new() });
+        }
+
+        let browser_slots = Arc::clone(
+            self.browsers
+                .lock()
+                .expect("queue lock")
+                .entry(browser.to_string())
+                .or_insert_with(|| Arc::new(Semaphore::new(self.max_concurrency))),
+        );
+
+        let key = LaneKey { browser: browser.to_string(), tab };
+        let lane_turn = {
+            let mut lanes = self.lanes.lock().expect("queue lock");
+            let lane = lanes.entry(key.clone()).or_insert_with(|| Lane {
+                turn: Arc::new(Semaphore::new(1)),
+                depth: 0,
+                running: None,
+            });
+            if lane.depth >= self.max_depth {
+                return Err(BrowseHandError::QueueFull(describe(&key)));
+            }
+            lane.depth += 1;
+            Arc::clone(&lane.turn)
+        };
+        // From here on the turn owns a place in the lane, even if waiting is
+        // cancelled.
+        let mut turn = Turn { queues: self, key: Some(key), permits: Vec::new() };
+
+        for slots in [lane_turn, browser_slots] {
+            let permit = tokio::select! {
+                permit = slots.acquire_owned() => permit.expect("queue semaphores are never closed"),
+                _ = cancel.cancelled() => return Err(BrowseHandError::Cancelled),
+            };
+            turn.permits.push(permit);
+        }
+
+        if let Some(lane) = self.lanes.lock().expect("queue lock").get_mut(turn.key.as_ref().expect("lane")) {
+            lane.running = Some(Running {
+                msg_type: msg_type.to_string(),
+                client: client.to_string(),
+                since: Instant::now(),
+            });
+        }
+        Ok(turn)
+    }
+
+    /// Every busy lane: what is running in it and how many requests wait.
+    pub fn snapshot(&self, browser: Option<&str>) -> Vec<Value> {
+        let lanes = self.lanes.lock().expect("queue lock");
+        let mut keys: Vec<&LaneKey> = lanes
+            .keys()
+            .filter(|key| browser.is_none_or(|name| key.browser == name))
+            .collect();
+        keys.sort_by(|a, b| (&a.browser, a.tab).cmp(&(&b.browser, b.tab)));
+        keys.into_iter()
+            .map(|key| {
+                let lane = &lanes[key];
+                let running = lane.running.as_ref().map(|r| {
+                    serde_json::json!({
+                        "type": r.msg_type,
+                        "client": r.client,
+                        "elapsedMs": r.since.elapsed().as_millis() as u64,
+                    })
+                });
+                serde_json::json!({
+                    "browser": key.browser,
+                    "tabId": key.tab,
+                    "queued": lane.depth - usize::from(running.is_some()),
+                    "running": running,
+                })
+            })
+            .collect()
+    }
+
+    /// Requests in flight per browser, against the concurrency limit.
+    pub fn in_flight(&self, browser: &str) -> usize {
+        self.browsers
+            .lock()
+            .expect("queue lock")
+            .get(browser)
+            .map_or(0, |slots| self.max_concurrency - slots.available_permits())
+    }
+
+    pub fn max_concurrency(&self) -> usize {
+        self.max_concurrency
+    }
+}
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        let Some(key) = &self.key else {
+            return;
+        };
+        let mut lanes = self.queues.lanes.lock().expect("queue lock");
+        if let Some(lane) = lanes.get_mut(key) {
+            lane.depth -= 1;
+            if !self.permits.is_empty() {
+                lane.running = None;
+            }
+            if lane.depth == 0 {
+                lanes.remove(key);
+            }
+        }
+    }
+}
+
+fn describe(key: &LaneKey) -> String {
+    match key.tab {
+        Some(tab) => format!("tab {} of {}", tab, key.browser),
+        None => format!("the active tab of {}", key.browser),
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::{Context, Result};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+use tokio_rustls::rustls::{self, ClientConfig, RootCertStore, ServerConfig};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+use tracing::info;
+
+use crate::config::{self, Config};
+
+/// Builds the acceptor for wss://, using the configured certificate or a
+/// self-signed one for localhost.
+pub fn acceptor(config: &Config) -> Result<TlsAcceptor> {
+    let (cert_path, key_path) = match (&config.cert, &config.key) {
+        (Some(cert), Some(key)) => (cert.clone(), key.clone()),
+        _ => self_signed()?,
+    };
+
+    let certs = read_certs(&cert_path)?;
+    let key = rustls_pemfile::private_key(&mut read(&key_path)?.as_slice())
+        .with_context(|| format!("invalid private key {}", key_path.display()))?
+        .with_context(|| format!("no private key in {}", key_path.display()))?;
+
+    let server_config = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("certificate and key do not match")?;
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+/// Opens a TLS connection to a running server, trusting only the certificate
+/// that server is configured with.
+pub async fn connect(config: &Config, addr: SocketAddr, tcp: TcpStream) -> Result<TlsStream<TcpStream>> {
+    let cert_path = match &config.cert {
+        Some(cert) => cert.clone(),
+        None => self_signed_paths().0,
+    };
+    let mut roots = RootCertStore::empty();
+    for cert in read_certs(&cert_path)? {
+        roots.add(cert).context("unusable server certificate")?;
+    }
+    let client_config = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    let name = if addr.ip().is_loopback() {
+        ServerName::try_from("localhost")?
+    } else {
+        ServerName::IpAddress(addr.ip().into())
+    };
+    TlsConnector::from(Arc::new(client_config))
+        .connect(name, tcp)
+        .await
+        .context("TLS handshake failed")
+}
+
+fn provider() -> Arc<rustls::crypto::CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn read(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("cannot read {}", path.display()))
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    rustls_pemfile::certs(&mut read(path)?.as_slice())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .with_context(|| format!("invalid certificate {}", path.display()))
+}
+
+fn self_signed_paths() -> (PathBuf, PathBuf) {
+    let dir = config::config_dir().join("tls");
+    (dir.join("cert.pem"), dir.join("key.pem"))
+}
+
+/// Returns the self-signed certificate in the config directory, generating it
+/// on first use. Browsers only accept it after it has been trusted once, e.g.
+/// by opening https://localhost:<port> and proceeding past the warning.
+fn self_signed() -> Result<(PathBuf, PathBuf)> {
+    let (cert_path, key_path) = self_signed_paths();
+    if cert_path.exists() && key_path.exists() {
+        return Ok((cert_path, key_path));
+    }
+
+    let rcgen::CertifiedKey { cert, key_pair } =
+        rcgen::generate_simple_self_signed(vec!["localhost".to_string(), "127.0.0.1".to_string()])
+            .context("failed to generate a self-signed certificate")?;
+    config::write_private(&key_path, &key_pair.serialize_pem())?;
+    std::fs::write(&cert_path, cert.pem())?;
+    info!("Generated self-signed certificate {}", cert_path.display());
+    Ok((cert_path, key_path))
+}
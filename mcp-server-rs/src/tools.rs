@@ -1,71 +1,272 @@
-use anyhow::{anyhow, Result};
+use crate::config::Config;
+use crate::error::{BrowseHandError, Result};
+use crate::export::{self, Format, SaveOptions};
+use base64::Engine;
 use serde_json::Value;
-use std::fs::{self, OpenOptions};
-use std::io::Write;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
 
-fn get_desktop_path() -> PathBuf {
-    dirs::desktop_dir().unwrap_or_else(|| PathBuf::from("."))
+/// The folder file tools work in. File names resolve inside it and may not
+/// leave it through `..` or a symlink; absolute paths are refused unless
+/// `allow_absolute_paths` is set. Every path must end in an allowed extension.
+#[derive(Debug)]
+pub struct OutputDir {
+    root: PathBuf,
+    allow_absolute: bool,
+    extensions: Vec<String>,
 }
 
-fn resolve_filepath(filename: &str) -> PathBuf {
-    if filename.contains('/') || filename.contains('\\') {
-        PathBuf::from(filename)
-    } else {
-        get_desktop_path().join(filename)
+impl OutputDir {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            root: config.output_dir(),
+            allow_absolute: config.allow_absolute_paths,
+            extensions: config
+                .file_extensions
+                .iter()
+                .map(|e| e.trim_start_matches('.').to_ascii_lowercase())
+                .collect(),
+        }
     }
-}
 
-pub fn save_csv(filename: &str, data: &Value, append: bool) -> Result<String> {
-    let filepath = resolve_filepath(filename);
-    
-    let arr = data.as_array().ok_or_else(|| anyhow!("data must be an array"))?;
-    
-    if arr.is_empty() {
-        return Err(anyhow!("data array is empty"));
+    /// Checks `filename` and returns the path it stands for. The output
+    /// folder is created if missing; folders below it are not.
+    pub fn resolve(&self, filename: &str) -> Result<PathBuf> {
+        let path = Path::new(filename);
+        let allowed = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| self.extensions.contains(&e.to_ascii_lowercase()));
+        if !allowed {
+            return Err(BrowseHandError::PathNotAllowed(format!(
+                "{} does not end in an allowed extension ({})",
+                filename,
+                self.extensions.join(", ")
+            )));
+        }
+
+        if path.has_root() || path.is_absolute() {
+            if self.allow_absolute {
+                return Ok(path.to_path_buf());
+            }
+            return Err(BrowseHandError::PathNotAllowed(format!(
+                "{} is absolute; use a name relative to {} (or set allow_absolute_paths)",
+                filename,
+                self.root.display()
+            )));
+        }
+        if !path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return Err(BrowseHandError::PathNotAllowed(format!("{} leaves {}", filename, self.root.display())));
+        }
+
+        fs::create_dir_all(&self.root)?;
+        let root = self.root.canonicalize()?;
+        // Follow whatever part of the path exists (without following a final
+        // symlink to find it) and make sure it stays in the folder.
+        let mut existing = root.join(path);
+        while fs::symlink_metadata(&existing).is_err() {
+            existing.pop();
+        }
+        let rest = root.join(path).strip_prefix(&existing).map(Path::to_path_buf).unwrap_or_default();
+        let real = existing.canonicalize().ok().filter(|real| real.starts_with(&root)).ok_or_else(|| {
+            BrowseHandError::PathNotAllowed(format!("{} leads out of {} through a symlink", filename, self.root.display()))
+        })?;
+        // Joining an empty path would add a trailing slash to an existing file.
+        if rest.as_os_str().is_empty() {
+            return Ok(real);
+        }
+        Ok(real.join(rest))
     }
-    
-    let headers: Vec<String> = arr[0]
-        .as_object()
-        .ok_or_else(|| anyhow!("each item must be an object"))?
-        .keys()
-        .cloned()
-        .collect();
-    
-    let file_exists = filepath.exists();
-    
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .append(append)
-        .truncate(!append)
-        .open(&filepath)?;
-    
-    if !append || !file_exists {
-        writeln!(file, "{}", headers.join(","))?;
+
+    /// [`resolve`](Self::resolve) for a file about to be written, creating
+    /// the folders it goes in.
+    fn create(&self, filename: &str) -> Result<PathBuf> {
+        let filepath = self.resolve(filename)?;
+        if let Some(parent) = filepath.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(filepath)
     }
-    
-    for row in arr {
-        let values: Vec<String> = headers
-            .iter()
-            .map(|h| {
-                let val = row.get(h).map(|v| match v {
-                    Value::String(s) => s.clone(),
-                    Value::Null => String::new(),
-                    other => other.to_string(),
-                }).unwrap_or_default();
-                format!("\"{}\"", val.replace('"', "\"\""))
-            })
-            .collect();
-        writeln!(file, "{}", values.join(","))?;
+}
+
+/// Saves `data` as `format`, or as the format of the file name's extension.
+pub fn save_data(
+    dir: &OutputDir,
+    filename: &str,
+    format: Option<Format>,
+    data: &Value,
+    options: &SaveOptions,
+) -> Result<String> {
+    let filepath = dir.create(filename)?;
+    let format = format.or_else(|| Format::from_path(&filepath)).ok_or_else(|| {
+        BrowseHandError::InvalidArgument(format!("cannot tell the format of '{}'; pass `format`", filename))
+    })?;
+    export::save(&filepath, format, data, options)?;
+    Ok(filepath.to_string_lossy().to_string())
+}
+
+pub fn save_csv(dir: &OutputDir, filename: &str, data: &Value, options: &SaveOptions) -> Result<String> {
+    save_data(dir, filename, Some(Format::Csv), data, options)
+}
+
+pub fn save_json(dir: &OutputDir, filename: &str, data: &Value, options: &SaveOptions) -> Result<String> {
+    save_data(dir, filename, Some(Format::Json), data, options)
+}
+
+fn guess_mime_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
     }
+}
+
+/// Reads a local file for a form file input. The extension cannot touch the
+/// filesystem, so the contents are shipped base64-encoded.
+pub fn read_upload(dir: &OutputDir, filename: &str) -> Result<Value> {
+    let filepath = dir.resolve(filename)?;
+    let bytes = fs::read(&filepath)?;
+    let name = filepath
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| filename.to_string());
     
-    Ok(filepath.to_string_lossy().to_string())
+    Ok(serde_json::json!({
+        "name": name,
+        "mimeType": guess_mime_type(&filepath),
+        "data": base64::engine::general_purpose::STANDARD.encode(bytes)
+    }))
 }
 
-pub fn save_json(filename: &str, data: &Value) -> Result<String> {
-    let filepath = resolve_filepath(filename);
-    let content = serde_json::to_string_pretty(data)?;
-    fs::write(&filepath, content)?;
+pub fn save_image(dir: &OutputDir, filename: &str, base64_data: &str) -> Result<String> {
+    let filepath = dir.create(filename)?;
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(base64_data)
+        .map_err(|e| BrowseHandError::InvalidArgument(format!("invalid image data: {}", e)))?;
+    fs::write(&filepath, bytes)?;
     Ok(filepath.to_string_lossy().to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An output folder in a fresh temporary directory, removed on drop.
+    struct Sandbox {
+        base: PathBuf,
+        dir: OutputDir,
+    }
+
+    impl Sandbox {
+        fn new(allow_absolute: bool) -> Self {
+            let base = std::env::temp_dir().join(format!("browsehand-test-{}", uuid::Uuid::new_v4().simple()));
+            let dir = OutputDir {
+                root: base.join("out"),
+                allow_absolute,
+                extensions: vec!["csv".into(), "json".into()],
+            };
+            Self { base, dir }
+        }
+
+        fn root(&self) -> PathBuf {
+            fs::create_dir_all(&self.dir.root).unwrap();
+            self.dir.root.canonicalize().unwrap()
+        }
+    }
+
+    impl Drop for Sandbox {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.base);
+        }
+    }
+
+    fn not_allowed(result: Result<PathBuf>) -> bool {
+        matches!(result, Err(BrowseHandError::PathNotAllowed(_)))
+    }
+
+    #[test]
+    fn rejects_parent_components() {
+        let sandbox = Sandbox::new(false);
+        assert!(not_allowed(sandbox.dir.resolve("../x.csv")));
+        assert!(not_allowed(sandbox.dir.resolve("sub/../../x.csv")));
+        assert!(not_allowed(sandbox.dir.resolve("sub/../x.csv")));
+    }
+
+    #[test]
+    fn absolute_paths_need_allow_absolute() {
+        let sandbox = Sandbox::new(false);
+        let outside = sandbox.base.join("elsewhere.csv");
+        assert!(not_allowed(sandbox.dir.resolve(outside.to_str().unwrap())));
+
+        let sandbox = Sandbox::new(true);
+        let outside = sandbox.base.join("elsewhere.csv");
+        assert_eq!(sandbox.dir.resolve(outside.to_str().unwrap()).unwrap(), outside);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_out_of_the_folder() {
+        let sandbox = Sandbox::new(false);
+        let root = sandbox.root();
+        let outside = sandbox.base.join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("file.csv"), root.join("file.csv")).unwrap();
+
+        assert!(not_allowed(sandbox.dir.resolve("link/x.csv")));
+        assert!(not_allowed(sandbox.dir.resolve("file.csv")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_symlinks_inside_the_folder() {
+        let sandbox = Sandbox::new(false);
+        let root = sandbox.root();
+        fs::create_dir_all(root.join("real")).unwrap();
+        std::os::unix::fs::symlink(root.join("real"), root.join("link")).unwrap();
+
+        assert_eq!(sandbox.dir.resolve("link/x.csv").unwrap(), root.join("real/x.csv"));
+    }
+
+    #[test]
+    fn resolves_paths_below_missing_folders() {
+        let sandbox = Sandbox::new(false);
+        let root = sandbox.root();
+        assert_eq!(sandbox.dir.resolve("a/b/c.csv").unwrap(), root.join("a/b/c.csv"));
+        assert_eq!(sandbox.dir.resolve("./d.csv").unwrap(), root.join("d.csv"));
+    }
+
+    #[test]
+    fn resolves_existing_files_to_themselves() {
+        let sandbox = Sandbox::new(false);
+        let root = sandbox.root();
+        fs::write(root.join("data.csv"), "a\n").unwrap();
+        // Compare by opening it: path equality ignores a trailing slash.
+        assert_eq!(fs::read_to_string(sandbox.dir.resolve("data.csv").unwrap()).unwrap(), "a\n");
+    }
+
+    #[test]
+    fn matches_extensions_ignoring_case() {
+        let sandbox = Sandbox::new(false);
+        let root = sandbox.root();
+        assert_eq!(sandbox.dir.resolve("DATA.CSV").unwrap(), root.join("DATA.CSV"));
+        assert_eq!(sandbox.dir.resolve("data.Json").unwrap(), root.join("data.Json"));
+        assert!(not_allowed(sandbox.dir.resolve("run.sh")));
+        assert!(not_allowed(sandbox.dir.resolve("noext")));
+    }
+}
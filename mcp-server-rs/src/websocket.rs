@@ -1,116 +1,685 @@
-use anyhow::{anyhow, Result};
-use futures_util::{SinkExt, StreamExt};
+use anyhow::{Context, Result};
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use rmcp::schemars;
+use serde::Deserialize;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{mpsc, oneshot, RwLock};
-use tokio_tungstenite::{accept_async, tungstenite::Message};
-use tracing::{error, info, warn};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot, watch, RwLock};
+use tokio_tungstenite::accept_hdr_async;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-type PendingRequests = HashMap<String, oneshot::Sender<Value>>;
+use crate::auth::Pairing;
+use crate::config::Config;
+use crate::error::BrowseHandError;
+use crate::events::EventLog;
+use crate::protocol::{self, Call, ClientHello, ClientMessage, Request as BridgeRequest, RequestMessage, ServerMessage, PROTOCOL_VERSION};
+use crate::queue::{self, RequestQueues};
 
+/// An in-flight request, tagged with the MCP client that issued it so the
+/// client's leftovers can be dropped when its session ends, and with the
+/// connection it was sent on so it fails as soon as that connection drops.
+struct PendingRequest {
+    client: String,
+    connection: String,
+    sender: oneshot::Sender<Value>,
+}
+
+type PendingRequests = HashMap<String, PendingRequest>;
+
+/// Session name used when an extension does not announce one in its hello.
+const DEFAULT_SESSION: &str = "default";
+
+/// Buffer between a control socket and the MCP service serving it.
+const CONTROL_BUFFER: usize = 64 * 1024;
+
+type WsError = tokio_tungstenite::tungstenite::Error;
+
+/// How long a new connection has to authenticate before it is closed.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A ping does no page work, so a slow answer already means trouble.
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Requests that only read from the browser, so sending them again after a
+/// reconnect cannot repeat a side effect.
+const IDEMPOTENT: &[&str] = &[
+    "read_content",
+    "extract_structured_data",
+    "get_current_url",
+    "get_dom_snapshot",
+    "detect_challenge",
+    "list_tabs",
+];
+
+/// How often an idempotent request is resent after losing its connection.
+const MAX_RETRIES: u32 = 2;
+
+/// Heartbeat intervals without any message from the extension after which its
+/// connection is considered dead and closed.
+const MISSED_HEARTBEATS: u32 = 3;
+
+/// Request id prefix of heartbeat pings; the rest is when they were sent.
+const HEARTBEAT_ID_PREFIX: &str = "heartbeat-";
+
+/// Who issues a bridge request: the MCP client it is tagged with and the
+/// cancellation token of the tool call it belongs to.
+#[derive(Clone)]
+pub struct Caller {
+    pub client: String,
+    pub cancel: CancellationToken,
+}
+
+// Which connected browser and which tab in it a request is routed to. Flattened
+// into tool arguments, so it deliberately has no doc comment (schemars would
+// copy it into every tool schema).
+#[derive(Debug, Clone, Default, Deserialize, schemars::JsonSchema)]
+pub struct Target {
+    #[schemars(description = "대상 탭 ID (선택사항, 기본값: 요청 시점의 활성 탭)")]
+    pub tab_id: Option<i64>,
+    #[schemars(description = "대상 브라우저 세션 이름 (list_browsers로 확인, 하나만 연결된 경우 생략 가능)")]
+    pub browser: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct BrowserSession {
+    connection_id: String,
+    sender: mpsc::UnboundedSender<Message>,
+    addr: SocketAddr,
+    connected_at: Instant,
+    extension_version: Option<String>,
+    /// Round trip of the last heartbeat ping, shared with the connection task.
+    latency: Arc<std::sync::Mutex<Option<Duration>>>,
+}
+
+impl BrowserSession {
+    fn to_json(&self, name: &str) -> Value {
+        serde_json::json!({
+            "name": name,
+            "address": self.addr.to_string(),
+            "connectedSecs": self.connected_at.elapsed().as_secs(),
+            "extensionVersion": self.extension_version,
+            "latencyMs": self.latency.lock().expect("latency lock").map(|d| d.as_millis() as u64),
+        })
+    }
+}
+
+/// Browser sessions live behind their own lock so a browser can (re)connect
+/// while tool calls hold the bridge and wait for it.
 pub struct ExtensionBridge {
-    sender: Option<mpsc::UnboundedSender<String>>,
+    sessions: std::sync::RwLock<HashMap<String, BrowserSession>>,
+    /// Bumped whenever a browser connects or disconnects.
+    changes: watch::Sender<()>,
     pending: Arc<RwLock<PendingRequests>>,
-    connected: bool,
+    queues: RequestQueues,
+    events: Arc<EventLog>,
+    default_timeout: Duration,
+    reconnect_grace: Duration,
+    heartbeat: Duration,
 }
 
 impl ExtensionBridge {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
         Self {
-            sender: None,
+            sessions: std::sync::RwLock::new(HashMap::new()),
+            changes: watch::Sender::new(()),
             pending: Arc::new(RwLock::new(HashMap::new())),
-            connected: false,
+            queues: RequestQueues::new(config.max_concurrency, config.max_queue_depth),
+            events: Arc::new(EventLog::new(config.event_buffer)),
+            default_timeout: config.request_timeout(),
+            reconnect_grace: config.reconnect_grace(),
+            heartbeat: config.heartbeat(),
         }
     }
 
+    fn sessions(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, BrowserSession>> {
+        self.sessions.read().expect("sessions lock")
+    }
+
     pub fn is_connected(&self) -> bool {
-        self.connected
+        !self.sessions().is_empty()
+    }
+
+    pub fn has_session(&self, name: &str) -> bool {
+        self.sessions().contains_key(name)
+    }
+
+    /// Describes every connected browser session, sorted by name.
+    pub fn list_sessions(&self) -> Vec<Value> {
+        let sessions = self.sessions();
+        let mut names: Vec<&String> = sessions.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| sessions[name].to_json(name))
+            .collect()
+    }
+
+    /// Describes the session a request for `browser` would go to.
+    pub fn describe_session(&self, browser: Option<&str>) -> Result<Value, BrowseHandError> {
+        self.session(browser).map(|(name, session)| session.to_json(&name))
+    }
+
+    fn session(&self, browser: Option<&str>) -> Result<(String, BrowserSession), BrowseHandError> {
+        let sessions = self.sessions();
+        let found = match browser {
+            Some(name) => sessions
+                .get_key_value(name)
+                .ok_or_else(|| BrowseHandError::BrowserNotFound(name.to_string())),
+            None => match sessions.len() {
+                0 => Err(BrowseHandError::NotConnected),
+                1 => Ok(sessions.iter().next().expect("one session")),
+                _ => {
+                    let mut names: Vec<&str> = sessions.keys().map(String::as_str).collect();
+                    names.sort();
+                    Err(BrowseHandError::AmbiguousBrowser(names.join(", ")))
+                }
+            },
+        };
+        found.map(|(name, session)| (name.clone(), session.clone()))
+    }
+
+    /// Like `session`, but while no matching browser is connected waits up to
+    /// the reconnect grace period for one, e.g. while the extension's service
+    /// worker restarts.
+    async fn wait_for_session(
+        &self,
+        browser: Option<&str>,
+        cancel: &CancellationToken,
+    ) -> Result<(String, BrowserSession), BrowseHandError> {
+        let deadline = tokio::time::Instant::now() + self.reconnect_grace;
+        let mut changes = self.changes.subscribe();
+        loop {
+            match self.session(browser) {
+                Err(BrowseHandError::NotConnected | BrowseHandError::BrowserNotFound(_))
+                    if tokio::time::Instant::now() < deadline =>
+                {
+                    tokio::select! {
+                        _ = changes.changed() => {}
+                        _ = tokio::time::sleep_until(deadline) => {}
+                        _ = cancel.cancelled() => return Err(BrowseHandError::Cancelled),
+                    }
+                }
+                found => return found,
+            }
+        }
+    }
+
+    fn register(&self, name: &str, session: BrowserSession) {
+        let replaced = self
+            .sessions
+            .write()
+            .expect("sessions lock")
+            .insert(name.to_string(), session);
+        if replaced.is_some() {
+            warn!("Browser session '{}' reconnected; replacing previous connection", name);
+        }
+        self.changes.send_replace(());
+    }
+
+    /// Removes the session if `connection_id` still owns it (a reconnect under
+    /// the same name may already have replaced it) and fails every request
+    /// still waiting on that connection.
+    async fn unregister(&self, name: &str, connection_id: &str) {
+        {
+            let mut sessions = self.sessions.write().expect("sessions lock");
+            if sessions.get(name).is_some_and(|s| s.connection_id == connection_id) {
+                sessions.remove(name);
+            }
+        }
+        self.changes.send_replace(());
+        self.pending
+            .write()
+            .await
+            .retain(|_, request| request.connection != connection_id);
+    }
+
+    /// Busy tab queues and requests in flight per browser, for `browser` or
+    /// for all of them.
+    pub fn queue_status(&self, browser: Option<&str>) -> Value {
+        let sessions = self.sessions();
+        let mut names: Vec<&String> = sessions
+            .keys()
+            .filter(|name| browser.is_none_or(|b| b == name.as_str()))
+            .collect();
+        names.sort();
+        let browsers: Vec<Value> = names
+            .into_iter()
+            .map(|name| {
+                serde_json::json!({
+                    "name": name,
+                    "inFlight": self.queues.in_flight(name),
+                    "maxConcurrency": self.queues.max_concurrency(),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "browsers": browsers,
+            "tabs": self.queues.snapshot(browser),
+        })
+    }
+
+    /// Events pushed by every connected browser.
+    pub fn events(&self) -> &EventLog {
+        &self.events
+    }
+
+    /// Drops every request still pending for `client`, e.g. after its MCP
+    /// session has ended. Late responses for them are then ignored.
+    pub async fn release_client(&self, client: &str) -> usize {
+        let mut pending = self.pending.write().await;
+        let before = pending.len();
+        pending.retain(|_, request| request.client != client);
+        before - pending.len()
     }
 
-    pub async fn send_and_wait(&self, msg_type: &str, payload: Value) -> Result<Value> {
-        let sender = self.sender.as_ref().ok_or_else(|| anyhow!("Extension not connected"))?;
+    /// How long to wait for the answer to a request: the default plus however
+    /// long the request is asked to wait in the page.
+    fn request_timeout(&self, request: &BridgeRequest) -> Duration {
+        let waits = match request {
+            BridgeRequest::Ping(_) => return PING_TIMEOUT,
+            BridgeRequest::WaitForElement(r) => r.timeout,
+            BridgeRequest::ClickElement(r) => r.wait_after,
+            BridgeRequest::TypeText(r) => u64::from(r.delay).saturating_mul(r.text.chars().count() as u64),
+            BridgeRequest::ScrollPage(r) if r.until_stable => r.settle_ms.saturating_mul(r.max_rounds),
+            _ => 0,
+        };
+        self.default_timeout + Duration::from_millis(waits)
+    }
+
+    /// Sends a request to the target browser and waits for its answer. Page
+    /// requests first wait their turn in the target tab's queue; the timeout
+    /// starts once the request is sent. If the wait times out or the caller
+    /// cancels, the extension is told to `cancel` the request so it stops
+    /// working on the page.
+    ///
+    /// A page request without a tab id is pinned to the tab active when it is
+    /// made, so it queues with requests naming that tab explicitly.
+    ///
+    /// Requests fail with `Disconnected` as soon as their connection drops.
+    /// Read-only ones are then sent again once the browser is back within the
+    /// reconnect grace period.
+    pub async fn send_and_wait<C: Call>(
+        &self,
+        caller: &Caller,
+        call: C,
+        target: &Target,
+    ) -> Result<C::Reply, BrowseHandError> {
+        let request = call.into();
+        let msg_type = request.kind();
+        let timeout = self.request_timeout(&request);
         
+        let mut retries = 0;
+        loop {
+            let (name, session) = self.wait_for_session(target.browser.as_deref(), &caller.cancel).await?;
+            let tab_id = self.target_tab(caller, &session, target.tab_id, msg_type).await?;
+            let _turn = self.queues.enter(&name, tab_id, msg_type, &caller.client, &caller.cancel).await?;
+            let outcome = self.request(caller, &session, &request, tab_id, timeout).await;
+            match outcome {
+                Err(BrowseHandError::Disconnected)
+                    if IDEMPOTENT.contains(&msg_type) && retries < MAX_RETRIES && !self.reconnect_grace.is_zero() =>
+                {
+                    retries += 1;
+                    warn!("Browser '{}' disconnected during {}; retrying after reconnect", name, msg_type);
+                }
+                outcome => return outcome.and_then(|reply| request.parse_reply::<C>(reply)),
+            }
+        }
+    }
+
+    /// The tab a request acts on: `tab_id` if given, else the active tab for
+    /// page requests, asked with a `ping`. None leaves the choice to the
+    /// extension; a failed ping falls back to that rather than failing the
+    /// request, which then waits in the lane of requests without a tab.
+    async fn target_tab(
+        &self,
+        caller: &Caller,
+        session: &BrowserSession,
+        tab_id: Option<i64>,
+        msg_type: &str,
+    ) -> Result<Option<i64>, BrowseHandError> {
+        if tab_id.is_some() || !queue::is_queued(msg_type) {
+            return Ok(tab_id);
+        }
+        let ping = BridgeRequest::from(protocol::Ping {});
+        let active = match self.request(caller, session, &ping, None, PING_TIMEOUT).await {
+            Ok(reply) => ping.parse_reply::<protocol::Ping>(reply).map(|pong| pong.active_tab),
+            Err(BrowseHandError::Cancelled) => return Err(BrowseHandError::Cancelled),
+            Err(e) => Err(e),
+        };
+        match active {
+            Ok(tab) => Ok(tab.map(|tab| tab.id)),
+            Err(e) => {
+                debug!("Could not resolve the active tab for {}: {}", msg_type, e);
+                Ok(None)
+            }
+        }
+    }
+
+    /// One attempt of `send_and_wait` on a specific connection.
+    async fn request(
+        &self,
+        caller: &Caller,
+        session: &BrowserSession,
+        request: &BridgeRequest,
+        tab_id: Option<i64>,
+        timeout: Duration,
+    ) -> Result<Value, BrowseHandError> {
         let request_id = Uuid::new_v4().to_string();
-        let message = serde_json::json!({
-            "type": msg_type,
-            "requestId": request_id,
-            "payload": payload
-        });
+        let message = RequestMessage {
+            request_id: &request_id,
+            tab_id,
+            request,
+        };
         
         let (tx, rx) = oneshot::channel();
         
         {
             let mut pending = self.pending.write().await;
-            pending.insert(request_id.clone(), tx);
+            pending.insert(
+                request_id.clone(),
+                PendingRequest {
+                    client: caller.client.clone(),
+                    connection: session.connection_id.clone(),
+                    sender: tx,
+                },
+            );
         }
         
-        sender.send(message.to_string())?;
+        let text = serde_json::to_string(&message).expect("requests serialize");
+        if session.sender.send(Message::Text(text)).is_err() {
+            self.pending.write().await.remove(&request_id);
+            return Err(BrowseHandError::Disconnected);
+        }
         
-        let timeout = tokio::time::timeout(
-            std::time::Duration::from_secs(30),
-            rx
-        ).await;
+        let outcome = tokio::select! {
+            response = rx => response.map_err(|_| BrowseHandError::Disconnected),
+            _ = tokio::time::sleep(timeout) => Err(BrowseHandError::Timeout),
+            _ = caller.cancel.cancelled() => Err(BrowseHandError::Cancelled),
+        };
         
         {
             let mut pending = self.pending.write().await;
             pending.remove(&request_id);
         }
         
-        match timeout {
-            Ok(Ok(response)) => Ok(response),
-            Ok(Err(_)) => Err(anyhow!("Response channel closed")),
-            Err(_) => Err(anyhow!("Request timeout")),
+        if let Err(BrowseHandError::Timeout | BrowseHandError::Cancelled) = outcome {
+            let cancel = ServerMessage::Cancel { request_id };
+            let _ = session.sender.send(Message::Text(cancel.to_text()));
         }
+        
+        outcome
+    }
+}
+
+/// Waits for the client's hello. Browser extensions register a session;
+/// `role: "control"` clients (the CLI) speak MCP over the socket instead.
+/// Returns None if the first message is anything else or does not arrive in
+/// time.
+async fn read_hello<S>(read: &mut S) -> Option<ClientHello>
+where
+    S: StreamExt<Item = Result<Message, WsError>> + Unpin,
+{
+    let Ok(Some(Ok(Message::Text(text)))) = tokio::time::timeout(HELLO_TIMEOUT, read.next()).await else {
+        return None;
+    };
+    match serde_json::from_str(&text).ok()? {
+        ClientMessage::Hello(hello) => Some(hello),
+        _ => None,
     }
 }
 
-async fn handle_connection(
-    stream: TcpStream,
+/// Tells the client why it is turned away and closes the socket.
+async fn reject<W>(mut write: W, message: ServerMessage, reason: &'static str)
+where
+    W: Sink<Message, Error = WsError> + Unpin,
+{
+    let _ = write.send(Message::Text(message.to_text())).await;
+    let _ = write
+        .send(Message::Close(Some(CloseFrame {
+            code: CloseCode::Policy,
+            reason: reason.into(),
+        })))
+        .await;
+}
+
+/// Relays MCP JSON-RPC between a control socket (one message per frame) and
+/// the service end of a duplex stream (one message per line).
+async fn serve_control<W, R>(
+    mut write: W,
+    mut read: R,
+    control: &mpsc::UnboundedSender<DuplexStream>,
+) -> Result<()>
+where
+    W: Sink<Message, Error = WsError> + Unpin,
+    R: Stream<Item = Result<Message, WsError>> + Unpin,
+{
+    let (local, remote) = tokio::io::duplex(CONTROL_BUFFER);
+    control
+        .send(remote)
+        .map_err(|_| anyhow::anyhow!("MCP control service is not running"))?;
+    let (local_read, mut local_write) = tokio::io::split(local);
+    let mut lines = BufReader::new(local_read).lines();
+
+    let outgoing = async {
+        while let Some(line) = lines.next_line().await? {
+            write.send(Message::Text(line)).await?;
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+    let incoming = async {
+        while let Some(msg) = read.next().await {
+            match msg? {
+                Message::Text(text) => {
+                    local_write.write_all(text.as_bytes()).await?;
+                    local_write.write_all(b"\n").await?;
+                }
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+
+    tokio::select! {
+        result = outgoing => result,
+        result = incoming => result,
+    }
+}
+
+async fn handle_connection<S>(
+    stream: S,
+    addr: SocketAddr,
     bridge: Arc<RwLock<ExtensionBridge>>,
-) -> Result<()> {
-    let ws_stream = accept_async(stream).await?;
+    pairing: Arc<Pairing>,
+    control: mpsc::UnboundedSender<DuplexStream>,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    // The Result type is fixed by tungstenite's handshake callback.
+    #[allow(clippy::result_large_err)]
+    let check_origin = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+        let origin = request.headers().get("origin").and_then(|o| o.to_str().ok());
+        if pairing.allows_origin(origin) {
+            return Ok(response);
+        }
+        warn!("Rejected WebSocket upgrade from {} (origin: {:?})", addr, origin);
+        let mut forbidden = ErrorResponse::new(Some("Forbidden origin".to_string()));
+        *forbidden.status_mut() = StatusCode::FORBIDDEN;
+        Err(forbidden)
+    };
+    let ws_stream = accept_hdr_async(stream, check_origin).await?;
     let (mut write, mut read) = ws_stream.split();
     
-    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    // Authenticate before touching the bridge, so a rejected socket can never
+    // replace a live session's sender.
+    let hello = read_hello(&mut read).await;
+    let Some(hello) = hello.filter(|h| pairing.verify(h.token.as_deref())) else {
+        warn!("Rejected unauthenticated WebSocket connection from {}", addr);
+        let message = ServerMessage::AuthError {
+            message: "Invalid or missing pairing token".to_string(),
+        };
+        reject(write, message, "unauthorized").await;
+        return Ok(());
+    };
     
-    {
-        let mut bridge_guard = bridge.write().await;
-        bridge_guard.sender = Some(tx);
-        bridge_guard.connected = true;
+    if hello.protocol != Some(PROTOCOL_VERSION) {
+        let theirs = hello.protocol.map_or_else(|| "none".to_string(), |v| v.to_string());
+        warn!(
+            "Rejected {} (version {}) from {}: protocol {}, server speaks {}",
+            hello.role.as_deref().unwrap_or("extension"),
+            hello.version.as_deref().unwrap_or("unknown"),
+            addr,
+            theirs,
+            PROTOCOL_VERSION
+        );
+        let message = ServerMessage::ProtocolError {
+            message: format!(
+                "Protocol version {} is not supported by BrowseHand server {} (protocol {}); update the extension or the server",
+                theirs,
+                env!("CARGO_PKG_VERSION"),
+                PROTOCOL_VERSION
+            ),
+            protocol: PROTOCOL_VERSION,
+        };
+        reject(write, message, "incompatible protocol").await;
+        return Ok(());
     }
     
-    info!("Chrome Extension connected via WebSocket");
+    if hello.role.as_deref() == Some("control") {
+        info!("Control client connected from {}", addr);
+        let reply = ServerMessage::Hello {
+            message: "MCP Server Connected!".to_string(),
+            session: None,
+            role: Some("control".to_string()),
+            protocol: PROTOCOL_VERSION,
+        };
+        write.send(Message::Text(reply.to_text())).await?;
+        return serve_control(write, read, &control).await;
+    }
     
-    let hello = serde_json::json!({
-        "type": "hello",
-        "message": "MCP Server Connected!"
-    });
-    write.send(Message::Text(hello.to_string())).await?;
+    let session_name = hello
+        .session
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .unwrap_or(DEFAULT_SESSION)
+        .to_string();
+    let extension_version = hello.version;
+    let connection_id = Uuid::new_v4().to_string();
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+    let heartbeat_tx = tx.clone();
+    let latency = Arc::new(std::sync::Mutex::new(None));
     
-    let pending = {
+    bridge.read().await.register(
+        &session_name,
+        BrowserSession {
+            connection_id: connection_id.clone(),
+            sender: tx,
+            addr,
+            connected_at: Instant::now(),
+            extension_version,
+            latency: Arc::clone(&latency),
+        },
+    );
+    
+    info!("Chrome Extension connected via WebSocket (session: {})", session_name);
+    
+    let hello = ServerMessage::Hello {
+        message: "MCP Server Connected!".to_string(),
+        session: Some(session_name.clone()),
+        role: None,
+        protocol: PROTOCOL_VERSION,
+    };
+    write.send(Message::Text(hello.to_text())).await?;
+    
+    let (pending, events, heartbeat_every) = {
         let bridge_guard = bridge.read().await;
-        Arc::clone(&bridge_guard.pending)
+        (
+            Arc::clone(&bridge_guard.pending),
+            Arc::clone(&bridge_guard.events),
+            bridge_guard.heartbeat,
+        )
     };
     
     let write_task = tokio::spawn(async move {
         while let Some(msg) = rx.recv().await {
-            if write.send(Message::Text(msg)).await.is_err() {
+            if write.send(msg).await.is_err() {
                 break;
             }
         }
     });
     
-    while let Some(msg) = read.next().await {
+    // Heartbeats are `ping` requests, not WebSocket pings: Chrome answers
+    // those itself even while the service worker is stuck, and only the
+    // worker answers a request. Their ids carry the milliseconds since
+    // `started` they were sent at, so each answer gives a round trip. An
+    // extension that sends nothing for several intervals is dead.
+    let started = Instant::now();
+    let mut last_seen = Instant::now();
+    let mut heartbeat = (!heartbeat_every.is_zero()).then(|| {
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + heartbeat_every, heartbeat_every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        interval
+    });
+    
+    loop {
+        let msg = tokio::select! {
+            msg = read.next() => msg,
+            _ = next_heartbeat(&mut heartbeat) => {
+                if last_seen.elapsed() > heartbeat_every * MISSED_HEARTBEATS {
+                    warn!(
+                        "No answer from session '{}' for {}s; closing dead connection",
+                        session_name,
+                        last_seen.elapsed().as_secs()
+                    );
+                    break;
+                }
+                let request_id = format!("{}{}", HEARTBEAT_ID_PREFIX, started.elapsed().as_millis());
+                let message = RequestMessage {
+                    request_id: &request_id,
+                    tab_id: None,
+                    request: &BridgeRequest::from(protocol::Ping {}),
+                };
+                let text = serde_json::to_string(&message).expect("requests serialize");
+                let _ = heartbeat_tx.send(Message::Text(text));
+                continue;
+            }
+        };
+        let Some(msg) = msg else {
+            break;
+        };
         match msg {
             Ok(Message::Text(text)) => {
+                last_seen = Instant::now();
                 if let Ok(json) = serde_json::from_str::<Value>(&text) {
-                    if let Some(request_id) = json.get("requestId").and_then(|v| v.as_str()) {
+                    if let Some(sent) = json["requestId"].as_str().and_then(|id| id.strip_prefix(HEARTBEAT_ID_PREFIX)) {
+                        if let Ok(sent) = sent.parse::<u64>() {
+                            let now = started.elapsed().as_millis() as u64;
+                            *latency.lock().expect("latency lock") = Some(Duration::from_millis(now.saturating_sub(sent)));
+                        }
+                    } else if let Some(request_id) = json.get("requestId").and_then(|v| v.as_str()) {
                         let mut pending_guard = pending.write().await;
-                        if let Some(sender) = pending_guard.remove(request_id) {
-                            let _ = sender.send(json);
+                        if let Some(request) = pending_guard.remove(request_id) {
+                            let _ = request.sender.send(json);
+                        }
+                    } else if json["type"] == "event" {
+                        match ClientMessage::deserialize(&json) {
+                            Ok(ClientMessage::Event(event)) => events.push(&session_name, event),
+                            Ok(_) => {}
+                            Err(e) => warn!("Ignoring malformed event from session '{}': {}", session_name, e),
                         }
                     }
                 }
@@ -126,28 +695,58 @@ async fn handle_connection(
     
     write_task.abort();
     
-    {
-        let mut bridge_guard = bridge.write().await;
-        bridge_guard.sender = None;
-        bridge_guard.connected = false;
-    }
+    bridge.read().await.unregister(&session_name, &connection_id).await;
     
-    warn!("Chrome Extension disconnected");
+    warn!("Chrome Extension disconnected (session: {})", session_name);
     
     Ok(())
 }
 
-pub async fn run_websocket_server(bridge: Arc<RwLock<ExtensionBridge>>) -> Result<()> {
-    let listener = TcpListener::bind("127.0.0.1:8765").await?;
-    info!("WebSocket server listening on ws://localhost:8765");
+async fn next_heartbeat(heartbeat: &mut Option<tokio::time::Interval>) {
+    match heartbeat {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Binds the bridge listener. Done before serving MCP so a port conflict can
+/// be reported as a startup error.
+pub async fn bind(config: &Config) -> Result<TcpListener> {
+    let addr = config.bridge_addr();
+    TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("cannot listen on {} for the extension bridge (is another BrowseHand running?)", addr))
+}
+
+pub async fn run_websocket_server(
+    listener: TcpListener,
+    tls: Option<TlsAcceptor>,
+    bridge: Arc<RwLock<ExtensionBridge>>,
+    pairing: Arc<Pairing>,
+    control: mpsc::UnboundedSender<DuplexStream>,
+) -> Result<()> {
+    let scheme = if tls.is_some() { "wss" } else { "ws" };
+    info!("WebSocket server listening on {}://{}", scheme, listener.local_addr()?);
     
     loop {
         let (stream, addr) = listener.accept().await?;
         info!("New connection from: {}", addr);
         
         let bridge_clone = Arc::clone(&bridge);
+        let pairing = Arc::clone(&pairing);
+        let tls = tls.clone();
+        let control = control.clone();
         tokio::spawn(async move {
-            if let Err(e) = handle_connection(stream, bridge_clone).await {
+            let result = match tls {
+                Some(acceptor) => match acceptor.accept(stream).await {
+                    Ok(stream) => handle_connection(stream, addr, bridge_clone, pairing, control).await,
+                    Err(e) => Err(e.into()),
+                },
+                None => handle_connection(stream, addr, bridge_clone, pairing, control).await,
+            };
+            if let Err(e) = result {
                 error!("Connection handler error: {}", e);
             }
         });